@@ -105,9 +105,19 @@ export const RUN_TS_CLIENT = task({
   },
 });
 
+export const RUN_RUST_PLUGIN_HOST = task({
+  name: "run-rust-plugin-host",
+  hiddenFromTaskList: true,
+  async run() {
+    // The host spawns the plugin binary, so it has to be built first.
+    await $`cargo build --bin rust_plugin`;
+    await $`cargo run --bin rust_plugin_host`;
+  },
+});
+
 export const RUN_ALL_TESTS = task({
   name: "test:all",
-  dependencies: [RUN_RUST_CLIENT, RUN_TS_CLIENT],
+  dependencies: [RUN_RUST_CLIENT, RUN_TS_CLIENT, RUN_RUST_PLUGIN_HOST],
   async run() {
     // Other tasks run, we can clean up.
     await quit();
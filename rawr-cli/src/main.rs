@@ -0,0 +1,155 @@
+use std::{fs, io, path::Path, process::ExitCode, time::Duration};
+
+use rawr::{DynamicClient, ServiceDescription, codegen::typescript::Codegen};
+use serde_json::Value;
+
+const USAGE: &str = "\
+Usage:
+  rawr call <url> <method> [args]
+      Call a method by name. `args` is a JSON array, e.g. '[\"World\"]'.
+  rawr describe <url>
+      Print the description of the service a server offers.
+  rawr codegen --schema <file> --lang ts --out <dir>
+      Generate bindings from a service description, e.g. the output of
+      `rawr describe`. Replaces <dir>, unless it holds anything but bindings.";
+
+/// How long to wait for the server to describe itself.
+const DESCRIBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let res = match args.as_slice() {
+        ["call", url, method] => call(url, method, "[]").await,
+        ["call", url, method, params] => call(url, method, params).await,
+        ["describe", url] => describe(url).await,
+        ["codegen", options @ ..] => codegen(options),
+        ["help" | "--help" | "-h"] => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        _ => Err(USAGE.to_string()),
+    };
+
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn connect(url: &str) -> Result<DynamicClient, String> {
+    let transport = rawr::transport::websocket::connect(url)
+        .await
+        .map_err(|err| format!("Failed to connect to {}: {}", url, err))?;
+    let (client, client_task) = DynamicClient::new(transport);
+    tokio::spawn(client_task);
+    Ok(client)
+}
+
+async fn call(url: &str, method: &str, args: &str) -> Result<(), String> {
+    let args: Value =
+        serde_json::from_str(args).map_err(|err| format!("Invalid arguments: {}", err))?;
+    let client = connect(url).await?;
+
+    // Check the arguments before sending them, if the server can describe itself
+    let client = match describe_service(&client).await {
+        Ok(description) => client.validate_with(description),
+        Err(_) => client,
+    };
+
+    let res = client
+        .call(method, args)
+        .await
+        .map_err(|err| format!("{} failed: {}", method, err))?;
+    println!("{}", pretty(&res));
+    Ok(())
+}
+
+async fn describe(url: &str) -> Result<(), String> {
+    let client = connect(url).await?;
+    let description = describe_service(&client)
+        .await
+        .map_err(|err| format!("Failed to describe {}: {}", url, err))?;
+    println!("{}", pretty(&description));
+    Ok(())
+}
+
+/// Ask the server which service it offers. Servers that don't answer
+/// `rawr.describe` drop the request, so give up after a while.
+async fn describe_service(client: &DynamicClient) -> Result<ServiceDescription, String> {
+    match tokio::time::timeout(DESCRIBE_TIMEOUT, client.describe()).await {
+        Ok(res) => res.map_err(|err| err.to_string()),
+        Err(_) => Err("server does not answer rawr.describe".to_string()),
+    }
+}
+
+fn codegen(options: &[&str]) -> Result<(), String> {
+    let mut schema = None;
+    let mut lang = None;
+    let mut out = None;
+    for option in options.chunks(2) {
+        match option {
+            ["--schema", value] => schema = Some(*value),
+            ["--lang", value] => lang = Some(*value),
+            ["--out", value] => out = Some(*value),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let (Some(schema), Some(out)) = (schema, out) else {
+        return Err(USAGE.to_string());
+    };
+
+    let json =
+        fs::read_to_string(schema).map_err(|err| format!("Failed to read {}: {}", schema, err))?;
+    let description: ServiceDescription = serde_json::from_str(&json)
+        .map_err(|err| format!("{} is not a service description: {}", schema, err))?;
+
+    // The code generator replaces the output directory, so don't let it take
+    // anything but bindings with it.
+    match only_bindings(Path::new(out)) {
+        Ok(true) => {}
+        Ok(false) => {
+            return Err(format!(
+                "{} contains files that aren't bindings, refusing to replace it",
+                out
+            ));
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(format!("Failed to read {}: {}", out, err)),
+    }
+
+    match lang.unwrap_or("ts") {
+        "ts" | "typescript" => Codegen::new()
+            .export_description(&description)
+            .export_to(out)
+            .try_run()
+            .map_err(|err| format!("Failed to write bindings to {}: {}", out, err)),
+        lang => Err(format!("Unsupported language: {}", lang)),
+    }
+}
+
+/// Whether `dir` only holds what the code generator writes: module directories
+/// with an `index.ts` in them.
+fn only_bindings(dir: &Path) -> io::Result<bool> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let is_bindings = if entry.file_type()?.is_dir() {
+            only_bindings(&entry.path())?
+        } else {
+            entry.file_name() == "index.ts"
+        };
+        if !is_bindings {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn pretty(value: &impl serde::Serialize) -> String {
+    serde_json::to_string_pretty(value).expect("JSON values serialize")
+}
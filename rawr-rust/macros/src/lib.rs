@@ -25,9 +25,15 @@ fn generate_struct_schema(
 ) -> proc_macro2::TokenStream {
     let generics = add_schema_bound(generics);
     let generic_field = generate_generic_field(&generics, name);
+    let params = generate_params_fingerprints(&generics);
 
     match &data.fields {
         Fields::Named(fields) => {
+            let fingerprints = fields.named.iter().map(|f| {
+                let field_name = f.ident.as_ref().unwrap().to_string();
+                let fingerprint = generate_field_fingerprint(&f.ty, name);
+                quote! { (#field_name, #fingerprint) }
+            });
             let fields = fields.named.iter().map(|f| {
                 let name = f.ident.as_ref().unwrap().to_string();
                 let ty = &f.ty;
@@ -43,6 +49,12 @@ fn generate_struct_schema(
 
             quote! {
                 impl #impl_generics ::rawr::Schema for #name #type_generics #where_clause {
+                    const FINGERPRINT: ::rawr::Fingerprint = ::rawr::Fingerprint::of_struct(
+                        stringify!(#name),
+                        #params,
+                        ::rawr::Fingerprint::map(&[#( #fingerprints ),*]),
+                    );
+
                     fn schema() -> ::rawr::SchemaDef {
                         ::rawr::SchemaDef::Struct(::rawr::StructDef {
                             name: stringify!(#name),
@@ -61,6 +73,7 @@ fn generate_struct_schema(
                 let ty = &f.ty;
                 quote! { ::rawr::SchemaPtr(<#ty as ::rawr::Schema>::schema) }
             });
+            let fingerprint = generate_unnamed_fingerprint(fields_unnamed, name);
 
             let shape = match fields_unnamed.unnamed.len() {
                 1 => {
@@ -85,6 +98,9 @@ fn generate_struct_schema(
 
             quote! {
                 impl #impl_generics ::rawr::Schema for #name #type_generics #where_clause {
+                    const FINGERPRINT: ::rawr::Fingerprint =
+                        ::rawr::Fingerprint::of_struct(stringify!(#name), #params, #fingerprint);
+
                     fn schema() -> ::rawr::SchemaDef {
                         ::rawr::SchemaDef::Struct(::rawr::StructDef {
                             name: stringify!(#name),
@@ -101,6 +117,12 @@ fn generate_struct_schema(
 
             quote! {
                 impl #impl_generics ::rawr::Schema for #name #type_generics #where_clause {
+                    const FINGERPRINT: ::rawr::Fingerprint = ::rawr::Fingerprint::of_struct(
+                        stringify!(#name),
+                        #params,
+                        ::rawr::Fingerprint::unit(),
+                    );
+
                     fn schema() -> ::rawr::SchemaDef {
                         ::rawr::SchemaDef::Struct(::rawr::StructDef {
                             name: stringify!(#name),
@@ -121,9 +143,26 @@ fn generate_enum_schema(
     attrs: &[Attribute],
     generics: &syn::Generics,
 ) -> proc_macro2::TokenStream {
+    let variant_fingerprints = data.variants.iter().map(|v| {
+        let variant_str = serde::parse_rename(&v.attrs).unwrap_or_else(|| v.ident.to_string());
+        let fingerprint = match &v.fields {
+            Fields::Named(named) => {
+                let fields = named.named.iter().map(|field| {
+                    let field_name = field.ident.as_ref().unwrap().to_string();
+                    let fingerprint = generate_field_fingerprint(&field.ty, name);
+                    quote! { (#field_name, #fingerprint) }
+                });
+                quote! { ::rawr::Fingerprint::map(&[#( #fields ),*]) }
+            }
+            Fields::Unnamed(unnamed) => generate_unnamed_fingerprint(unnamed, name),
+            Fields::Unit => quote! { ::rawr::Fingerprint::unit() },
+        };
+        quote! { (#variant_str, #fingerprint) }
+    });
+
     let variants_iter = data.variants.iter().map(|v| {
-        let variant_name = &v.ident;
-        let variant_str = variant_name.to_string();
+        // Named as serialized.
+        let variant_str = serde::parse_rename(&v.attrs).unwrap_or_else(|| v.ident.to_string());
         match &v.fields {
             Fields::Named(named) => {
                 let fields_iter = named.named.iter().map(|field| {
@@ -215,10 +254,18 @@ fn generate_enum_schema(
 
     let generics = add_schema_bound(generics);
     let generic_field = generate_generic_field(&generics, name);
+    let params = generate_params_fingerprints(&generics);
     let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
 
     quote! {
         impl #impl_generics ::rawr::Schema for #name #type_generics #where_clause {
+            const FINGERPRINT: ::rawr::Fingerprint = ::rawr::Fingerprint::of_enum(
+                stringify!(#name),
+                #params,
+                #rep,
+                &[#( #variant_fingerprints ),*],
+            );
+
             fn schema() -> ::rawr::SchemaDef {
                 ::rawr::SchemaDef::Enum(::rawr::EnumDef {
                     name: stringify!(#name),
@@ -270,6 +317,9 @@ fn generate_generic_field(generics: &syn::Generics, name: &syn::Ident) -> proc_m
                 #(
                     struct #param_idents;
                     impl ::rawr::Schema for #param_idents {
+                        const FINGERPRINT: ::rawr::Fingerprint =
+                            ::rawr::Fingerprint::parameter(stringify!(#param_idents));
+
                         fn schema() -> ::rawr::SchemaDef {
                             ::rawr::SchemaDef::GenericParameter(stringify!(#param_idents))
                         }
@@ -281,6 +331,54 @@ fn generate_generic_field(generics: &syn::Generics, name: &syn::Ident) -> proc_m
     }
 }
 
+/// Fingerprints of the arguments of the generic parameters, as a slice.
+fn generate_params_fingerprints(generics: &syn::Generics) -> proc_macro2::TokenStream {
+    let params = generics.params.iter().filter_map(|param| match param {
+        GenericParam::Type(TypeParam { ident, .. }) => {
+            Some(quote! { <#ident as ::rawr::Schema>::FINGERPRINT })
+        }
+        _ => None,
+    });
+    quote! { &[#( #params ),*] }
+}
+
+/// Fingerprint of the shape of a tuple-like struct or variant.
+fn generate_unnamed_fingerprint(
+    fields: &syn::FieldsUnnamed,
+    name: &syn::Ident,
+) -> proc_macro2::TokenStream {
+    let fingerprints: Vec<_> = fields
+        .unnamed
+        .iter()
+        .map(|field| generate_field_fingerprint(&field.ty, name))
+        .collect();
+    match &fingerprints[..] {
+        [fingerprint] => quote! { ::rawr::Fingerprint::newtype(#fingerprint) },
+        _ => quote! { ::rawr::Fingerprint::tuple(&[#( #fingerprints ),*]) },
+    }
+}
+
+/// Fingerprint of a field of type `ty` in the definition of `name`. A field that
+/// contains the type itself is fingerprinted by how its type is written, as its
+/// fingerprint would depend on itself.
+fn generate_field_fingerprint(ty: &syn::Type, name: &syn::Ident) -> proc_macro2::TokenStream {
+    if mentions(quote!(#ty), name) {
+        let ty = quote!(#ty).to_string();
+        quote! { ::rawr::Fingerprint::recursive(#ty) }
+    } else {
+        quote! { <#ty as ::rawr::Schema>::FINGERPRINT }
+    }
+}
+
+/// Whether `tokens` refer to `name`, or to `Self`.
+fn mentions(tokens: proc_macro2::TokenStream, name: &syn::Ident) -> bool {
+    tokens.into_iter().any(|token| match token {
+        proc_macro2::TokenTree::Ident(ident) => ident == *name || ident == "Self",
+        proc_macro2::TokenTree::Group(group) => mentions(group.stream(), name),
+        _ => false,
+    })
+}
+
 /// Adds `Schema` bound to all generic parameters.
 fn add_schema_bound(generics: &syn::Generics) -> syn::Generics {
     let mut generics = generics.clone();
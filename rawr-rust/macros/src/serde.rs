@@ -38,3 +38,28 @@ pub fn parse_attr(attrs: &[Attribute]) -> Option<(String, String)> {
     }
     None
 }
+
+/// Parses `#[serde(rename = "name")]`, e.g. of an enum variant.
+pub fn parse_rename(attrs: &[Attribute]) -> Option<String> {
+    for attr in attrs {
+        if attr.path().is_ident("serde") {
+            struct RenameAttr(String);
+
+            impl Parse for RenameAttr {
+                fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+                    let ident: syn::Ident = input.parse()?;
+                    if ident != "rename" {
+                        return Err(syn::Error::new(ident.span(), "expected `rename`"));
+                    }
+                    input.parse::<syn::Token![=]>()?;
+                    Ok(RenameAttr(input.parse::<syn::LitStr>()?.value()))
+                }
+            }
+
+            if let Ok(RenameAttr(name)) = attr.parse_args::<RenameAttr>() {
+                return Some(name);
+            }
+        }
+    }
+    None
+}
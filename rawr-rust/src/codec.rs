@@ -0,0 +1,228 @@
+//! Wire formats used by the built-in [`transport`](crate::transport)s to turn
+//! [`Envelope`](crate::Envelope)s into bytes and back.
+
+use serde::{Serialize, de::DeserializeOwned};
+use thiserror::Error;
+
+#[cfg(feature = "postcard")]
+mod variant_index;
+
+/// Encodes values into bytes and decodes them back.
+///
+/// Both sides of a connection must use the same codec.
+pub trait Codec: Clone {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CodecError>;
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError>;
+
+    /// Whether encoded values are always valid UTF-8. Transports that distinguish
+    /// between text and binary messages, such as WebSocket, use this to pick one.
+    fn is_text(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Error)]
+#[error(transparent)]
+pub struct CodecError(pub Box<dyn std::error::Error + Send + Sync>);
+
+//// JSON
+
+/// JSON codec. This is what `rawr-typescript` speaks.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+#[cfg(feature = "json")]
+impl Codec for Json {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        serde_json::to_vec(value).map_err(|e| CodecError(e.into()))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        serde_json::from_slice(bytes).map_err(|e| CodecError(e.into()))
+    }
+
+    fn is_text(&self) -> bool {
+        true
+    }
+}
+
+//// MessagePack
+
+/// [MessagePack](https://msgpack.org) codec.
+///
+/// Structs are encoded as maps with their field names, like in JSON. Encoding
+/// them as arrays would be more compact, but serde can't decode unit variants
+/// of adjacently tagged enums from an array.
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePack;
+
+#[cfg(feature = "msgpack")]
+impl Codec for MessagePack {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        rmp_serde::to_vec_named(value).map_err(|e| CodecError(e.into()))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        rmp_serde::from_slice(bytes).map_err(|e| CodecError(e.into()))
+    }
+}
+
+//// CBOR
+
+/// [CBOR](https://cbor.io) codec.
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cbor;
+
+#[cfg(feature = "cbor")]
+impl Codec for Cbor {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(value, &mut bytes).map_err(|e| CodecError(e.into()))?;
+        Ok(bytes)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        ciborium::from_reader(bytes).map_err(|e| CodecError(e.into()))
+    }
+}
+
+//// Postcard
+
+/// [Postcard](https://postcard.jamesmunns.com) codec. It is the most compact
+/// one, but it isn't self-describing: both sides must agree on the exact types,
+/// and types that need to be deserialized without knowing their layout upfront
+/// (e.g. `#[serde(untagged)]` enums or internally tagged enums) are not supported.
+/// Neither are unit variants of adjacently tagged enums, as serde doesn't encode
+/// any content for them.
+#[cfg(feature = "postcard")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Postcard;
+
+#[cfg(feature = "postcard")]
+impl Codec for Postcard {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        postcard::to_stdvec(value).map_err(|e| CodecError(e.into()))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        let mut deserializer = postcard::Deserializer::from_bytes(bytes);
+        T::deserialize(variant_index::VariantIndex(&mut deserializer))
+            .map_err(|e| CodecError(e.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{Envelope, Packet, RequestError};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(tag = "method", content = "payload")]
+    #[allow(non_camel_case_types)]
+    enum Request {
+        say_hello((String,)),
+        telemetry((Vec<f64>, u32)),
+    }
+
+    #[allow(dead_code)]
+    fn roundtrip(codec: impl Codec) {
+        let mut request = Packet::new(42, Request::telemetry((vec![0.5; 16], 7)));
+        request.meta.insert("trace".to_string(), "abc".to_string());
+        let request = Envelope::Packet(request);
+        let bytes = codec.encode(&request).unwrap();
+        let decoded: Envelope<Request> = codec.decode(&bytes).unwrap();
+        let Envelope::Packet(decoded) = decoded else {
+            panic!("expected a packet");
+        };
+        assert_eq!(decoded.id, 42);
+        assert_eq!(decoded.data, Request::telemetry((vec![0.5; 16], 7)));
+        assert_eq!(decoded.meta["trace"], "abc");
+
+        let response: Envelope<crate::Result<Request>> =
+            Envelope::Packet(Packet::new(7, Err(RequestError::Cancelled)));
+        let bytes = codec.encode(&response).unwrap();
+        let decoded: Envelope<crate::Result<Request>> = codec.decode(&bytes).unwrap();
+        assert!(matches!(
+            decoded,
+            Envelope::Packet(Packet {
+                id: 7,
+                data: Err(RequestError::Cancelled),
+                ..
+            })
+        ));
+
+        let bytes = codec.encode(&Envelope::<Request>::End(3)).unwrap();
+        let decoded: Envelope<Request> = codec.decode(&bytes).unwrap();
+        assert!(matches!(decoded, Envelope::End(3)));
+
+        let bytes = codec.encode(&Envelope::<Request>::Cancel(3)).unwrap();
+        let decoded: Envelope<Request> = codec.decode(&bytes).unwrap();
+        assert!(matches!(decoded, Envelope::Cancel(3)));
+
+        let meta = crate::Meta::from([("trace".to_string(), "def".to_string())]);
+        let bytes = codec.encode(&Envelope::Notification(5u32, meta)).unwrap();
+        let decoded: Envelope<u32> = codec.decode(&bytes).unwrap();
+        let Envelope::Notification(5, meta) = decoded else {
+            panic!("expected a notification");
+        };
+        assert_eq!(meta["trace"], "def");
+
+        let bytes = codec.encode(&Envelope::<u32>::Ping).unwrap();
+        let decoded: Envelope<u32> = codec.decode(&bytes).unwrap();
+        assert!(matches!(decoded, Envelope::Ping));
+
+        let batch = Envelope::Batch(vec![Packet::new(1, 2u32), Packet::new(3, 4)]);
+        let bytes = codec.encode(&batch).unwrap();
+        let decoded: Envelope<u32> = codec.decode(&bytes).unwrap();
+        let Envelope::Batch(packets) = decoded else {
+            panic!("expected a batch");
+        };
+        let packets: Vec<_> = packets.iter().map(|p| (p.id, p.data)).collect();
+        assert_eq!(packets, [(1, 2), (3, 4)]);
+
+        // Malformed input is an error, not a panic.
+        assert!(
+            codec
+                .decode::<Envelope<Request>>(&[0xff, 0x00, 0x13])
+                .is_err()
+        );
+    }
+
+    /// Plain packets must stay readable by peers that don't know about envelopes.
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json_envelope_is_packet() {
+        let envelope = Envelope::Packet(Packet::new(1, 2));
+        let json = String::from_utf8(Json.encode(&envelope).unwrap()).unwrap();
+        assert_eq!(json, r#"{"id":1,"data":2}"#);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json_roundtrip() {
+        roundtrip(Json);
+    }
+
+    #[test]
+    #[cfg(feature = "msgpack")]
+    fn test_msgpack_roundtrip() {
+        roundtrip(MessagePack);
+    }
+
+    #[test]
+    #[cfg(feature = "cbor")]
+    fn test_cbor_roundtrip() {
+        roundtrip(Cbor);
+    }
+
+    #[test]
+    #[cfg(feature = "postcard")]
+    fn test_postcard_roundtrip() {
+        roundtrip(Postcard);
+    }
+}
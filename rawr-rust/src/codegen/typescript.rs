@@ -1,25 +1,26 @@
 use std::{
-    borrow::Cow,
-    collections::{BTreeSet, HashMap},
-    fs,
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
     path::{Path, PathBuf},
 };
 
 use crate::{
-    EnumDef, EnumRepr, GenericDef, PrimitiveDef, Schema, SchemaDef, Shape, StructDef, VariantDef,
+    Definition, FieldDescription, Fingerprint, Representation, Schema, SchemaDef,
+    ServiceDescription, ShapeDescription, TypeDescription, TypeRef, VariantDescription,
 };
 
-type StringCow = Cow<'static, str>;
-
 pub struct Codegen {
     // NOTE: We're using BTreeSet instead of HashSet to ensure a consistent
     // ordering of the generated bindings (e.g., order of the generated imports),
-    // which is important for the snapshot tests.
-    //
-    // In case the order of the generated bindings still changes (e.g., due to
-    // *probably* nondeterministic `SchemaPtr` function pointer in `SchemaDef`),
-    // consider sorting beforehand by `name`, etc.
+    // which is important for the snapshot tests. Definitions and imports are
+    // sorted by name before they are generated as well.
     schemas: BTreeSet<SchemaDef>,
+    /// Types of services that were described rather than compiled in, e.g. by
+    /// a running server.
+    descriptions: Vec<TypeDescription>,
+    /// Constants, e.g. fingerprints, by the module they are exported from. Values
+    /// are TypeScript expressions.
+    constants: BTreeMap<&'static str, BTreeMap<&'static str, String>>,
 
     output_path: PathBuf,
 }
@@ -29,6 +30,8 @@ impl Codegen {
         Codegen {
             output_path: PathBuf::new(),
             schemas: BTreeSet::new(),
+            descriptions: Vec::new(),
+            constants: BTreeMap::new(),
         }
     }
 
@@ -40,18 +43,57 @@ impl Codegen {
 
     /// Export schema. All of its dependent schemas will be exported as well.
     pub fn export_schema(&mut self, schema: SchemaDef) {
-        // Skip if the schema has already been exported
-        if self.schemas.contains(&schema) {
-            return;
-        }
-
-        self.export_dependencies(schema);
         self.schemas.insert(schema);
     }
 
-    /// Recursively export all type dependencies of a schema
-    fn export_dependencies(&mut self, schema: SchemaDef) {
-        schema.visit_dependencies(|dep| self.export_schema(dep));
+    /// Export every type of a described service, e.g. one that was answered to
+    /// [`DESCRIBE_METHOD`](crate::DESCRIBE_METHOD) by a running server.
+    pub fn export_description(mut self, description: &ServiceDescription) -> Self {
+        self.descriptions.extend(description.types.iter().cloned());
+        self
+    }
+
+    /// Export a constant `name` holding the [`Fingerprint`] of `T` from the
+    /// bindings of `module_path`, e.g. for the handshake of a client.
+    ///
+    /// ```typescript
+    /// export const TEST_FINGERPRINT = "8c0e7f2b1d3a5964";
+    /// ```
+    pub fn export_fingerprint<T: Schema>(
+        mut self,
+        module_path: &'static str,
+        name: &'static str,
+    ) -> Self {
+        let fingerprint = format!("\"{}\"", Fingerprint::of::<T>());
+        self.export_constant_expr(module_path, name, fingerprint);
+        self
+    }
+
+    /// Export a constant `name` holding `value`, encoded as JSON, from the
+    /// bindings of `module_path`, e.g. the [`ServiceDescription`] a server
+    /// answers [`DESCRIBE_METHOD`](crate::DESCRIBE_METHOD) with.
+    #[cfg(feature = "json")]
+    pub fn export_constant(
+        mut self,
+        module_path: &'static str,
+        name: &'static str,
+        value: &impl serde::Serialize,
+    ) -> Self {
+        let json = serde_json::to_string_pretty(value).expect("Failed to encode constant");
+        self.export_constant_expr(module_path, name, json);
+        self
+    }
+
+    fn export_constant_expr(
+        &mut self,
+        module_path: &'static str,
+        name: &'static str,
+        expr: String,
+    ) {
+        self.constants
+            .entry(module_path)
+            .or_default()
+            .insert(name, expr);
     }
 
     pub fn export_to(mut self, output_path: impl AsRef<Path>) -> Self {
@@ -59,108 +101,143 @@ impl Codegen {
         self
     }
 
+    /// Like [`Codegen::try_run`], but panics if the bindings can't be written.
     pub fn run(self) {
+        self.try_run().expect("Failed to write bindings")
+    }
+
+    /// Write the bindings, replacing whatever was in the output directory.
+    pub fn try_run(self) -> io::Result<()> {
         // Clear the output directory. If it didn't exist yet, ignore the error.
         let _ = fs::remove_dir_all(&self.output_path);
 
-        // Group schemas by module
-        let mut modules: HashMap<&'static str, BTreeSet<SchemaDef>> = HashMap::new();
-        for schema in &self.schemas {
-            match schema {
-                SchemaDef::Primitive(..)
-                | SchemaDef::Sequence(..)
-                | SchemaDef::Tuple(..)
-                | SchemaDef::GenericParameter(..) => {}
-                SchemaDef::Struct(struct_def) => {
-                    modules
-                        .entry(struct_def.module_path)
-                        .or_insert_with(BTreeSet::new)
-                        .insert(schema.generic_schema().unwrap_or(*schema));
-                }
-                SchemaDef::Enum(enum_def) => {
-                    modules
-                        .entry(enum_def.module_path)
-                        .or_insert_with(BTreeSet::new)
-                        .insert(schema.generic_schema().unwrap_or(*schema));
-                }
-            }
+        // Describe every type once, whether it was compiled in or described
+        let mut types: BTreeMap<(&str, &str), TypeDescription> = BTreeMap::new();
+        let compiled = TypeDescription::all_of(self.schemas.iter().copied());
+        for ty in compiled.iter().chain(&self.descriptions) {
+            types
+                .entry((&ty.module_path, &ty.name))
+                .or_insert_with(|| ty.clone());
+        }
+
+        // Group types by module
+        let mut modules: BTreeMap<&str, Vec<&TypeDescription>> = BTreeMap::new();
+        for ty in types.values() {
+            modules.entry(&ty.module_path).or_default().push(ty);
+        }
+
+        // Modules that only export constants
+        for module_path in self.constants.keys() {
+            modules.entry(module_path).or_default();
         }
 
-        for (module_path, definitions) in modules {
-            self.generate_module(
-                module_path,
-                &definitions.iter().cloned().collect::<Vec<_>>(),
-            );
+        for (module_path, mut definitions) in modules {
+            definitions.sort_by_key(|ty| sort_key(&ty.definition, &ty.name, &ty.module_path));
+            self.generate_module(&types, module_path, &definitions)?;
         }
+        Ok(())
     }
 
-    fn generate_module(&self, module_path: &'static str, definitions: &[SchemaDef]) {
-        let module_dir = self.create_module_directory(module_path);
+    fn generate_module(
+        &self,
+        types: &BTreeMap<(&str, &str), TypeDescription>,
+        module_path: &str,
+        definitions: &[&TypeDescription],
+    ) -> io::Result<()> {
+        let module_dir = self.create_module_directory(module_path)?;
         let output_file_path = module_dir.join("index.ts");
 
-        let imports = self.generate_imports(module_path, definitions);
+        let imports = self.generate_imports(types, module_path, definitions);
         let defs = self.generate_definitions(definitions);
+        let constants = self.generate_constants(module_path);
 
-        let file_content = format!("{}{}", imports, defs);
-        fs::write(&output_file_path, file_content).expect("Failed to write module bindings");
+        let file_content = format!("{}{}{}", imports, defs, constants);
+        fs::write(&output_file_path, file_content)
     }
 
-    fn create_module_directory(&self, module_path: &str) -> PathBuf {
+    fn create_module_directory(&self, module_path: &str) -> io::Result<PathBuf> {
         let module_dir = Path::new(&self.output_path).join(module_path.replace("::", "/"));
-        fs::create_dir_all(&module_dir).expect("Failed to create module directory");
-        module_dir
+        fs::create_dir_all(&module_dir)?;
+        Ok(module_dir)
     }
 
-    fn generate_imports(&self, module_path: &str, schema_defs: &[SchemaDef]) -> String {
+    fn generate_imports(
+        &self,
+        types: &BTreeMap<(&str, &str), TypeDescription>,
+        module_path: &str,
+        definitions: &[&TypeDescription],
+    ) -> String {
         //// Create a list of all type dependencies that are not in this module
 
-        type Imports = BTreeSet<SchemaDef>;
+        type Imports<'a> = BTreeSet<(bool, &'a str, &'a str)>;
 
         let mut dependencies: Imports = BTreeSet::new();
 
-        fn visit(dependencies: &mut Imports, def: SchemaDef, module_path: &&str) {
+        fn visit<'a>(
+            dependencies: &mut Imports<'a>,
+            types: &'a BTreeMap<(&str, &str), TypeDescription>,
+            ty: &'a TypeRef,
+            module_path: &str,
+        ) {
             // If the type depends on other types, for example `T` in `Option<T>`
             // or `T` and `U` in the tuple `(T, U)`, add them as a dependency
-            for dep in def.generic_dependencies() {
-                visit(dependencies, dep.get(), module_path);
+            let params = match ty {
+                TypeRef::Sequence(item) => std::slice::from_ref(&**item),
+                TypeRef::Tuple(items) => items,
+                TypeRef::Named { params, .. } => params,
+                TypeRef::Primitive(_) | TypeRef::Parameter(_) => &[],
+            };
+            for param in params {
+                visit(dependencies, types, param, module_path);
             }
 
             // If the type is not in the same module, add it as a dependency
-            if let Some(schema_module) = def.module_path() {
-                if *module_path != schema_module {
-                    // If the type is generic, add the generic schema to avoid
-                    // duplicate imports (BTreeSet will filter out duplicates)
-                    match def.generic_schema() {
-                        Some(generic_def) => dependencies.insert(generic_def),
-                        None => dependencies.insert(def),
-                    };
-                }
+            if let TypeRef::Named {
+                name,
+                module_path: ty_module,
+                ..
+            } = ty
+                && module_path != ty_module
+            {
+                let definition = types.get(&(ty_module.as_str(), name.as_str()));
+                let definition = definition.map(|ty| &ty.definition);
+                dependencies.insert(sort_key(definition, name, ty_module));
             }
         }
 
-        for schema in schema_defs {
-            schema.visit_dependencies(|dep| visit(&mut dependencies, dep, &module_path));
+        for definition in definitions {
+            for dep in definition_dependencies(&definition.definition) {
+                visit(&mut dependencies, types, dep, module_path);
+            }
         }
 
         //// Generate import statements
 
         let mut imports = String::new();
 
-        for dep in dependencies {
+        for (_, name, dep_module) in dependencies {
             imports.push_str(&format!(
                 "import {{ type {} }} from \"{}\";\n",
-                dep.name().unwrap(),
-                compute_relative_path_from_module(module_path, dep.module_path().unwrap())
+                name,
+                compute_relative_path_from_module(module_path, dep_module)
             ));
         }
 
         imports
     }
 
-    fn generate_definitions(&self, schema_defs: &[SchemaDef]) -> String {
+    fn generate_definitions(&self, definitions: &[&TypeDescription]) -> String {
+        let mut buf = String::new();
+        for definition in definitions {
+            self.generate_definition(&mut buf, definition);
+        }
+        buf
+    }
+
+    fn generate_constants(&self, module_path: &str) -> String {
         let mut buf = String::new();
-        for schema in schema_defs {
-            self.generate_definition(&mut buf, schema);
+        for (name, expr) in self.constants.get(module_path).into_iter().flatten() {
+            buf.push_str(&format!("export const {} = {};\n", name, expr));
         }
         buf
     }
@@ -174,11 +251,16 @@ impl Codegen {
     ///     b: Option<string>;
     /// };
     /// ```
-    fn generate_definition(&self, buf: &mut String, schema: &SchemaDef) {
-        match schema {
-            SchemaDef::Struct(struct_def) => self.generate_struct_definition(struct_def, buf),
-            SchemaDef::Enum(enum_def) => self.generate_enum_definition(enum_def, buf),
-            _ => {}
+    fn generate_definition(&self, buf: &mut String, ty: &TypeDescription) {
+        let generics = self.generate_generic_params(&ty.params);
+        match &ty.definition {
+            Definition::Struct(shape) => {
+                self.generate_struct_definition(&ty.name, &generics, shape, buf)
+            }
+            Definition::Enum {
+                representation,
+                variants,
+            } => self.generate_enum_definition(&ty.name, &generics, representation, variants, buf),
         }
     }
 
@@ -190,104 +272,70 @@ impl Codegen {
     ///      b: Option<string>; // <- `Option<string>` is a type
     /// };
     /// ```
-    fn generate_type(&self, schema: SchemaDef) -> StringCow {
-        match schema {
-            SchemaDef::Primitive(ref prim) => self.primitive_to_type(prim).into(),
-            SchemaDef::Sequence(ref schema) => {
-                let ty = self.generate_type(schema.get());
-                format!("{}[]", ty).into()
-            }
-            SchemaDef::Tuple(ref schemas) => {
-                let ts_types: Vec<StringCow> = schemas
-                    .iter()
-                    .map(|schema| self.generate_type(schema.get()))
-                    .collect();
-                format!("[{}]", ts_types.join(", ")).into()
-            }
-            SchemaDef::Struct(ref struct_type) => {
-                let generics = self.generate_generic_params(&struct_type.generic);
-                format!("{}{}", struct_type.name, generics).into()
-            }
-            SchemaDef::Enum(enum_def) => {
-                let generics = self.generate_generic_params(&enum_def.generic);
-                format!("{}{}", enum_def.name, generics).into()
+    fn generate_type(&self, ty: &TypeRef) -> String {
+        match ty {
+            TypeRef::Primitive(name) => self.primitive_to_type(name).to_string(),
+            TypeRef::Sequence(item) => format!("{}[]", self.generate_type(item)),
+            TypeRef::Tuple(items) => format!("[{}]", self.generate_types(items)),
+            TypeRef::Named { name, params, .. } => {
+                let params: Vec<String> = params.iter().map(|ty| self.generate_type(ty)).collect();
+                format!("{}{}", name, self.generate_generic_params(&params))
             }
-            SchemaDef::GenericParameter(param) => param.into(),
+            TypeRef::Parameter(param) => param.clone(),
         }
     }
 
-    fn primitive_to_type(&self, primitive: &PrimitiveDef) -> &'static str {
+    fn generate_types(&self, types: &[TypeRef]) -> String {
+        let ts_types: Vec<String> = types.iter().map(|ty| self.generate_type(ty)).collect();
+        ts_types.join(", ")
+    }
+
+    fn primitive_to_type(&self, primitive: &str) -> &'static str {
         match primitive {
-            PrimitiveDef::U8
-            | PrimitiveDef::U16
-            | PrimitiveDef::U32
-            | PrimitiveDef::U64
-            | PrimitiveDef::I8
-            | PrimitiveDef::I16
-            | PrimitiveDef::I32
-            | PrimitiveDef::I64
-            | PrimitiveDef::F32
-            | PrimitiveDef::F64 => "number",
-            PrimitiveDef::Unit => "null",
-            PrimitiveDef::Bool => "boolean",
-            PrimitiveDef::Char => "string",
-            PrimitiveDef::String => "string",
+            "u8" | "u16" | "u32" | "u64" | "i8" | "i16" | "i32" | "i64" | "f32" | "f64" => "number",
+            "()" => "null",
+            "bool" => "boolean",
+            "char" | "String" => "string",
+            // Unknown to this version, e.g. described by a newer server.
+            _ => "unknown",
         }
     }
 
-    fn generate_generic_params(&self, generic: &Option<GenericDef>) -> String {
-        if let Some(generic) = generic {
-            let mut param_names = Vec::new();
-
-            for param in generic.params {
-                param_names.push(self.generate_type(param.get()));
-            }
-
-            if !param_names.is_empty() {
-                format!("<{}>", param_names.join(", "))
-            } else {
-                "".to_string()
-            }
+    fn generate_generic_params(&self, params: &[String]) -> String {
+        if !params.is_empty() {
+            format!("<{}>", params.join(", "))
         } else {
             "".to_string()
         }
     }
 
-    fn generate_struct_definition(&self, struct_def: &StructDef, buf: &mut String) {
-        let generics = self.generate_generic_params(&struct_def.generic);
-        match struct_def.shape {
-            Shape::Unit => {
-                buf.push_str(&format!(
-                    "export type {}{} = null;\n",
-                    struct_def.name, generics
-                ));
+    fn generate_struct_definition(
+        &self,
+        name: &str,
+        generics: &str,
+        shape: &ShapeDescription,
+        buf: &mut String,
+    ) {
+        match shape {
+            ShapeDescription::Unit => {
+                buf.push_str(&format!("export type {}{} = null;\n", name, generics));
             }
-            Shape::Newtype(ref schema) => {
-                let ty = self.generate_type(schema.get());
-                buf.push_str(&format!(
-                    "export type {}{} = {};\n",
-                    struct_def.name, generics, ty
-                ));
+            ShapeDescription::Newtype(ty) => {
+                let ty = self.generate_type(ty);
+                buf.push_str(&format!("export type {}{} = {};\n", name, generics, ty));
             }
-            Shape::Tuple(ref fields) => {
-                let ts_types: Vec<StringCow> = fields
-                    .iter()
-                    .map(|schema| self.generate_type(schema.get()))
-                    .collect();
+            ShapeDescription::Tuple(fields) => {
                 buf.push_str(&format!(
                     "export type {}{} = [{}];\n",
-                    struct_def.name,
+                    name,
                     generics,
-                    ts_types.join(", ")
+                    self.generate_types(fields)
                 ));
             }
-            Shape::Map(ref fields) => {
-                buf.push_str(&format!(
-                    "export type {}{} = {{\n",
-                    struct_def.name, generics
-                ));
-                for field in *fields {
-                    let ty = self.generate_type(field.schema.get());
+            ShapeDescription::Map(fields) => {
+                buf.push_str(&format!("export type {}{} = {{\n", name, generics));
+                for field in fields {
+                    let ty = self.generate_type(&field.schema);
                     buf.push_str(&format!("  {}: {};\n", field.name, ty));
                 }
                 buf.push_str("};\n");
@@ -295,82 +343,86 @@ impl Codegen {
         }
     }
 
-    fn generate_enum_definition(&self, enum_def: &EnumDef, buf: &mut String) {
-        let generics = self.generate_generic_params(&enum_def.generic);
-        buf.push_str(&format!("export type {}{} =\n", enum_def.name, generics));
-        for variant in enum_def.variants {
-            buf.push_str(&self.generate_enum_variant(&enum_def.representation, variant));
+    fn generate_enum_definition(
+        &self,
+        name: &str,
+        generics: &str,
+        repr: &Representation,
+        variants: &[VariantDescription],
+        buf: &mut String,
+    ) {
+        buf.push_str(&format!("export type {}{} =\n", name, generics));
+        for variant in variants {
+            buf.push_str(&self.generate_enum_variant(repr, variant));
         }
         buf.push_str(";\n");
     }
 
-    fn generate_enum_variant(&self, repr: &EnumRepr, variant: &VariantDef) -> String {
-        match variant.shape {
-            Shape::Unit => match repr {
-                EnumRepr::External => format!("  | \"{}\"\n", variant.name),
-                EnumRepr::Adjacent { tag, content: _ } => {
-                    format!("  | {{ {}: \"{}\" }}\n", tag, variant.name)
-                }
-            },
-            Shape::Newtype(ref schema) => {
-                let ty = self.generate_type(schema.get());
-                match repr {
-                    EnumRepr::External => format!("  | {{ \"{}\": {} }}\n", variant.name, ty),
-                    EnumRepr::Adjacent { tag, content } => {
-                        format!(
-                            "  | {{ {}: \"{}\"; {}: {} }}\n",
-                            tag, variant.name, content, ty
-                        )
+    fn generate_enum_variant(&self, repr: &Representation, variant: &VariantDescription) -> String {
+        let content = match &variant.shape {
+            ShapeDescription::Unit => {
+                return match repr {
+                    Representation::External => format!("  | \"{}\"\n", variant.name),
+                    Representation::Adjacent { tag, content: _ } => {
+                        format!("  | {{ {}: \"{}\" }}\n", tag, variant.name)
                     }
-                }
+                };
             }
-            Shape::Tuple(ref fields) => {
-                let ts_types: Vec<StringCow> = fields
-                    .iter()
-                    .map(|schema| self.generate_type(schema.get()))
-                    .collect();
-                match repr {
-                    EnumRepr::External => {
-                        format!(
-                            "  | {{ \"{}\": [{}] }}\n",
-                            variant.name,
-                            ts_types.join(", ")
-                        )
-                    }
-                    EnumRepr::Adjacent { tag, content } => {
-                        format!(
-                            "  | {{ {}: \"{}\"; {}: [{}] }}\n",
-                            tag,
-                            variant.name,
-                            content,
-                            ts_types.join(", ")
-                        )
-                    }
-                }
-            }
-            Shape::Map(ref fields) => {
+            ShapeDescription::Newtype(ty) => self.generate_type(ty),
+            ShapeDescription::Tuple(fields) => format!("[{}]", self.generate_types(fields)),
+            ShapeDescription::Map(fields) => {
                 let field_strs: Vec<String> = fields
                     .iter()
-                    .map(|field| {
-                        let ty = self.generate_type(field.schema.get());
-                        format!("{}: {}", field.name, ty)
-                    })
+                    .map(|field| format!("{}: {}", field.name, self.generate_type(&field.schema)))
                     .collect();
-                let fields_str = field_strs.join(", ");
-                match repr {
-                    EnumRepr::External => {
-                        format!("  | {{ \"{}\": {{ {} }} }}\n", variant.name, fields_str)
-                    }
-                    EnumRepr::Adjacent { tag, content } => {
-                        format!(
-                            "  | {{ {}: \"{}\"; {}: {{ {} }} }}\n",
-                            tag, variant.name, content, fields_str
-                        )
-                    }
-                }
+                format!("{{ {} }}", field_strs.join(", "))
+            }
+        };
+        match repr {
+            Representation::External => format!("  | {{ \"{}\": {} }}\n", variant.name, content),
+            Representation::Adjacent { tag, content: key } => {
+                format!(
+                    "  | {{ {}: \"{}\"; {}: {} }}\n",
+                    tag, variant.name, key, content
+                )
+            }
+        }
+    }
+}
+
+/// Order of definitions and imports: enums before structs, then by name.
+fn sort_key<'a>(
+    definition: impl Into<Option<&'a Definition>>,
+    name: &'a str,
+    module_path: &'a str,
+) -> (bool, &'a str, &'a str) {
+    let is_struct = !matches!(definition.into(), Some(Definition::Enum { .. }));
+    (is_struct, name, module_path)
+}
+
+/// Types used directly by a definition.
+fn definition_dependencies(definition: &Definition) -> Vec<&TypeRef> {
+    fn shape_dependencies<'a>(shape: &'a ShapeDescription, deps: &mut Vec<&'a TypeRef>) {
+        match shape {
+            ShapeDescription::Unit => {}
+            ShapeDescription::Newtype(ty) => deps.push(ty),
+            ShapeDescription::Tuple(fields) => deps.extend(fields),
+            ShapeDescription::Map(fields) => {
+                deps.extend(fields.iter().map(|field: &FieldDescription| &field.schema))
+            }
+        }
+    }
+
+    let mut deps = Vec::new();
+    match definition {
+        Definition::Struct(shape) => shape_dependencies(shape, &mut deps),
+        Definition::Enum { variants, .. } => {
+            for variant in variants {
+                shape_dependencies(&variant.shape, &mut deps);
             }
         }
     }
+    deps
 }
 
 /// Computes relative typescript import path from `current` to `target` rust module.
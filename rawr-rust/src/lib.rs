@@ -1,6 +1,11 @@
+// Lets `#[derive(Schema)]` refer to `::rawr` within this crate too.
+extern crate self as rawr;
+
+pub mod codec;
 pub mod codegen;
 pub mod schema;
 pub mod service;
+pub mod transport;
 
 pub use rawr_macros::Schema;
 pub use schema::*;
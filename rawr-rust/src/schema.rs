@@ -1,6 +1,15 @@
-use std::fmt::{self, Debug, Formatter};
+use std::{
+    fmt::{self, Debug, Display, Formatter},
+    num::ParseIntError,
+    str::FromStr,
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
 
 pub trait Schema {
+    /// See [`Fingerprint`].
+    const FINGERPRINT: Fingerprint;
+
     fn schema() -> SchemaDef;
 }
 
@@ -152,6 +161,8 @@ macro_rules! impl_schema_for_primitive {
     ($($t:ty => $variant:ident),*) => {
         $(
             impl Schema for $t {
+                const FINGERPRINT: Fingerprint = Fingerprint::primitive(stringify!($t));
+
                 fn schema() -> SchemaDef {
                     SchemaDef::Primitive(PrimitiveDef::$variant)
                 }
@@ -180,30 +191,40 @@ impl_schema_for_primitive!(
 //// Array-like
 
 impl<T: Schema> Schema for Vec<T> {
+    const FINGERPRINT: Fingerprint = Fingerprint::sequence(T::FINGERPRINT);
+
     fn schema() -> SchemaDef {
         SchemaDef::Sequence(SchemaPtr(T::schema))
     }
 }
 
 impl<T: Schema> Schema for [T] {
+    const FINGERPRINT: Fingerprint = Fingerprint::sequence(T::FINGERPRINT);
+
     fn schema() -> SchemaDef {
         SchemaDef::Sequence(SchemaPtr(T::schema))
     }
 }
 
 impl<T: Schema> Schema for &[T] {
+    const FINGERPRINT: Fingerprint = Fingerprint::sequence(T::FINGERPRINT);
+
     fn schema() -> SchemaDef {
         SchemaDef::Sequence(SchemaPtr(T::schema))
     }
 }
 
 impl<T: Schema> Schema for &mut [T] {
+    const FINGERPRINT: Fingerprint = Fingerprint::sequence(T::FINGERPRINT);
+
     fn schema() -> SchemaDef {
         SchemaDef::Sequence(SchemaPtr(T::schema))
     }
 }
 
 impl<const N: usize, T: Schema> Schema for [T; N] {
+    const FINGERPRINT: Fingerprint = Fingerprint::sequence(T::FINGERPRINT);
+
     fn schema() -> SchemaDef {
         SchemaDef::Sequence(SchemaPtr(T::schema))
     }
@@ -215,6 +236,8 @@ macro_rules! impl_schema_for_tuples {
     ($(($($name:ident),+)),+) => {
         $(
             impl<$($name: Schema),+> Schema for ($($name,)+) {
+                const FINGERPRINT: Fingerprint = Fingerprint::tuple(&[$($name::FINGERPRINT),+]);
+
                 fn schema() -> SchemaDef {
                     SchemaDef::Tuple(&[$(SchemaPtr($name::schema)),+])
                 }
@@ -361,9 +384,21 @@ pub struct GenericDef {
 //// Result
 
 impl<T: Schema, E: Schema> Schema for Result<T, E> {
+    const FINGERPRINT: Fingerprint = Fingerprint::of_enum(
+        "Result",
+        &[T::FINGERPRINT, E::FINGERPRINT],
+        EnumRepr::External,
+        &[
+            ("Ok", Fingerprint::newtype(T::FINGERPRINT)),
+            ("Err", Fingerprint::newtype(E::FINGERPRINT)),
+        ],
+    );
+
     fn schema() -> SchemaDef {
         struct __T;
         impl Schema for __T {
+            const FINGERPRINT: Fingerprint = Fingerprint::parameter("T");
+
             fn schema() -> SchemaDef {
                 SchemaDef::GenericParameter("T")
             }
@@ -371,6 +406,8 @@ impl<T: Schema, E: Schema> Schema for Result<T, E> {
 
         struct __E;
         impl Schema for __E {
+            const FINGERPRINT: Fingerprint = Fingerprint::parameter("E");
+
             fn schema() -> SchemaDef {
                 SchemaDef::GenericParameter("E")
             }
@@ -401,7 +438,203 @@ impl<T: Schema, E: Schema> Schema for Result<T, E> {
 //// PathBuf
 
 impl Schema for std::path::PathBuf {
+    const FINGERPRINT: Fingerprint = String::FINGERPRINT;
+
+    fn schema() -> SchemaDef {
+        SchemaDef::Primitive(PrimitiveDef::String)
+    }
+}
+
+//// Box
+
+impl<T: Schema> Schema for Box<T> {
+    const FINGERPRINT: Fingerprint = T::FINGERPRINT;
+
+    fn schema() -> SchemaDef {
+        T::schema()
+    }
+}
+
+//// Fingerprint
+
+/// Stable hash of a schema and of every schema it depends on, e.g. of the
+/// requests and responses of a service.
+///
+/// Two ends of a connection with the same fingerprint agree on what goes over
+/// the wire. Anything that changes the encoding changes the fingerprint: adding,
+/// removing or renaming a type, field or variant, changing the type of a field
+/// or the representation of an enum. Moving a type to another module doesn't.
+///
+/// Every type's fingerprint is a constant, [`Schema::FINGERPRINT`], computed at
+/// compile time from the fingerprints of the types it is made of. A type that
+/// contains itself, e.g. in a `Vec<Self>` field, refers to itself by the type
+/// of that field. Types that contain each other have no fingerprint: using it
+/// is a compile error.
+///
+/// It is encoded as a string of 16 hex digits, which JavaScript numbers can't
+/// hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Fingerprint(pub u64);
+
+impl Fingerprint {
+    pub const fn of<T: Schema + ?Sized>() -> Self {
+        T::FINGERPRINT
+    }
+
+    /// Fingerprint of a primitive type named `name`.
+    pub const fn primitive(name: &str) -> Self {
+        EMPTY.bytes(b"p").str(name)
+    }
+
+    /// Fingerprint of a sequence of `item`s.
+    pub const fn sequence(item: Fingerprint) -> Self {
+        EMPTY.bytes(b"s").with(item)
+    }
+
+    /// Fingerprint of a tuple of `items`. A tuple-like struct or variant has
+    /// this shape too.
+    pub const fn tuple(items: &[Fingerprint]) -> Self {
+        EMPTY.bytes(b"t").all(items)
+    }
+
+    /// Fingerprint of a generic parameter named `name`.
+    pub const fn parameter(name: &str) -> Self {
+        EMPTY.bytes(b"g").str(name)
+    }
+
+    /// Shape of a unit-like struct or variant.
+    pub const fn unit() -> Self {
+        EMPTY.bytes(b"u")
+    }
+
+    /// Shape of a newtype-like struct or variant.
+    pub const fn newtype(inner: Fingerprint) -> Self {
+        EMPTY.bytes(b"n").with(inner)
+    }
+
+    /// Shape of a struct or variant with named `fields`.
+    pub const fn map(fields: &[(&str, Fingerprint)]) -> Self {
+        let mut hash = EMPTY.bytes(b"m").len(fields.len());
+        let mut i = 0;
+        while i < fields.len() {
+            hash = hash.str(fields[i].0).with(fields[i].1);
+            i += 1;
+        }
+        hash
+    }
+
+    /// Stands in for the fingerprint of a field whose type contains the type
+    /// being defined, written as `ty`.
+    pub const fn recursive(ty: &str) -> Self {
+        EMPTY.bytes(b"r").str(ty)
+    }
+
+    /// Fingerprint of a struct named `name`, with the arguments of its generic
+    /// parameters, if any, and its `shape`.
+    pub const fn of_struct(name: &str, params: &[Fingerprint], shape: Fingerprint) -> Self {
+        EMPTY
+            .bytes(b"n")
+            .str(name)
+            .all(params)
+            .bytes(b"S")
+            .with(shape)
+    }
+
+    /// Like [`of_struct`](Fingerprint::of_struct), for an enum with the shape of
+    /// each variant.
+    pub const fn of_enum(
+        name: &str,
+        params: &[Fingerprint],
+        representation: EnumRepr,
+        variants: &[(&str, Fingerprint)],
+    ) -> Self {
+        let mut hash = EMPTY.bytes(b"n").str(name).all(params).bytes(b"E");
+        hash = match representation {
+            EnumRepr::External => hash.bytes(b"x"),
+            EnumRepr::Adjacent { tag, content } => hash.bytes(b"a").str(tag).str(content),
+        };
+        hash = hash.len(variants.len());
+        let mut i = 0;
+        while i < variants.len() {
+            hash = hash.str(variants[i].0).with(variants[i].1);
+            i += 1;
+        }
+        hash
+    }
+
+    /// Hashes `bytes` with FNV-1a. Unlike `std::hash`, it is guaranteed to give
+    /// the same result everywhere.
+    const fn bytes(self, bytes: &[u8]) -> Self {
+        let mut hash = self.0;
+        let mut i = 0;
+        while i < bytes.len() {
+            hash ^= bytes[i] as u64;
+            hash = hash.wrapping_mul(FNV_PRIME);
+            i += 1;
+        }
+        Fingerprint(hash)
+    }
+
+    const fn len(self, len: usize) -> Self {
+        self.bytes(&(len as u32).to_le_bytes())
+    }
+
+    const fn str(self, s: &str) -> Self {
+        self.len(s.len()).bytes(s.as_bytes())
+    }
+
+    const fn with(self, other: Fingerprint) -> Self {
+        self.bytes(&other.0.to_le_bytes())
+    }
+
+    const fn all(self, others: &[Fingerprint]) -> Self {
+        let mut hash = self.len(others.len());
+        let mut i = 0;
+        while i < others.len() {
+            hash = hash.with(others[i]);
+            i += 1;
+        }
+        hash
+    }
+}
+
+impl Schema for Fingerprint {
+    const FINGERPRINT: Fingerprint = String::FINGERPRINT;
+
     fn schema() -> SchemaDef {
         SchemaDef::Primitive(PrimitiveDef::String)
     }
 }
+
+impl Display for Fingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for Fingerprint {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u64::from_str_radix(s, 16).map(Fingerprint)
+    }
+}
+
+impl Serialize for Fingerprint {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Fingerprint {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Fingerprint of nothing, which every other one starts from.
+const EMPTY: Fingerprint = Fingerprint(FNV_OFFSET_BASIS);
@@ -1,21 +1,68 @@
 use std::{
+    collections::BTreeMap,
+    pin::pin,
     sync::Arc,
     sync::atomic::{AtomicU32, Ordering},
 };
 
-use futures::{StreamExt, channel::oneshot};
+use futures::{
+    Sink, SinkExt, Stream, StreamExt,
+    channel::{mpsc, oneshot},
+    future::{self, AbortHandle},
+    lock::Mutex,
+    stream,
+};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::dashmap::DashMap;
+use crate::dashmap::{DashMap, mapref::entry::Entry};
 
+pub mod batch;
 pub mod channel;
+pub mod context;
+pub mod describe;
+#[cfg(feature = "json")]
+pub mod dynamic;
+pub mod envelope;
+pub mod handshake;
+pub mod heartbeat;
+pub mod limit;
+pub mod middleware;
+pub mod peer;
+pub mod reconnect;
+pub mod server;
+pub mod shutdown;
+pub mod spawn;
+pub mod streaming;
 
+pub use batch::*;
 pub use channel::*;
+pub use context::*;
+pub use describe::*;
+#[cfg(feature = "json")]
+pub use dynamic::*;
+pub use envelope::*;
+pub use handshake::*;
+pub use heartbeat::*;
+pub use limit::*;
+pub use middleware::*;
+pub use peer::*;
+pub use reconnect::*;
+pub use server::*;
+pub use shutdown::*;
+pub use spawn::*;
+pub use streaming::*;
 
 pub type Result<T> = std::result::Result<T, RequestError>;
-pub type ClientTransport<Req, Res> = (Tx<Packet<Req>>, Rx<Packet<Result<Res>>>);
-pub type ServerTransport<Req, Res> = (Rx<Packet<Req>>, Tx<Packet<Result<Res>>>);
+/// Client half of the in-memory [`transport`]. [`AbstractClient`] accepts any
+/// `Sink`/`Stream` pair, these are just the ones [`transport`] returns.
+pub type ClientTransport<Req, Res> = (Tx<Envelope<Req>>, Rx<Envelope<Result<Res>>>);
+/// Server half of the in-memory [`transport`].
+pub type ServerTransport<Req, Res> = (Rx<Envelope<Req>>, Tx<Envelope<Result<Res>>>);
+pub type BoundedClientTransport<Req, Res> =
+    (BoundedTx<Envelope<Req>>, BoundedRx<Envelope<Result<Res>>>);
+pub type BoundedServerTransport<Req, Res> =
+    (BoundedRx<Envelope<Req>>, BoundedTx<Envelope<Result<Res>>>);
 
 #[derive(Debug, Clone, Error)]
 pub enum TransportError {
@@ -25,6 +72,8 @@ pub enum TransportError {
     ReceiveError,
     #[error("Connection closed")]
     Closed,
+    #[error("Channel is full")]
+    Full,
 }
 
 #[derive(Debug, Clone, Error, Serialize, Deserialize)]
@@ -33,9 +82,32 @@ pub enum RequestError {
     TransportClosed,
     #[error("Request was cancelled")]
     Cancelled,
+    /// The server was handling too many requests to take this one, see
+    /// [`ConcurrencyLimit`].
+    #[error("Server is overloaded")]
+    Overloaded,
+    /// The other end speaks another version of the protocol, or of the service,
+    /// see [`Handshake`].
+    #[error("Incompatible peer: {0}")]
+    Incompatible(String),
+    /// The request doesn't match the service, e.g. the arguments of a
+    /// [`DynamicClient`] call.
+    #[error("Invalid request: {0}")]
+    Invalid(String),
+    /// No response arrived in time, e.g. within the timeout of a
+    /// [`DynamicClient`].
+    #[error("Request timed out")]
+    TimedOut,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Metadata sent along with a [`Packet`] or a notification, like the headers of an HTTP request:
+/// trace ids, auth tokens, locales or deadlines. Keys and values are strings, so
+/// that every codec can carry them.
+pub type Meta = BTreeMap<String, String>;
+
+/// Serialized as a map of `id`, `data` and, unless it is empty, `meta`. See
+/// [`Envelope`].
+#[derive(Clone)]
 pub struct Packet<P> {
     /// Unique identifier used to send the response back to the correct caller,
     /// when multiple calls to the same method were made.
@@ -44,61 +116,353 @@ pub struct Packet<P> {
     /// packet is a request and the return value of the rpc call when packet is a
     /// response.
     pub data: P,
+    /// Empty unless set by the sender, e.g. in an [`Interceptor`].
+    pub meta: Meta,
+}
+
+impl<P> Packet<P> {
+    /// A packet without metadata.
+    pub fn new(id: u32, data: P) -> Self {
+        Packet {
+            id,
+            data,
+            meta: Meta::new(),
+        }
+    }
 }
 
 pub struct AbstractClient<Req, Res> {
     counter: Arc<AtomicU32>,
-    requests: Arc<DashMap<u32, oneshot::Sender<Packet<Result<Res>>>>>,
-    server_tx: Tx<Packet<Req>>,
+    requests: Arc<DashMap<u32, Pending<Res>>>,
+    // NOTE: All clones share a single sender, so that a bounded channel keeps its
+    // capacity no matter how many times the client has been cloned.
+    server_tx: Arc<Mutex<BoundedTx<Envelope<Req>>>>,
+    // Unbounded, so that a dropped `ResponseStream` can cancel its request
+    // without waiting.
+    cancel_tx: mpsc::UnboundedSender<u32>,
+    // Unbounded, so that notifications can be sent without waiting.
+    notify_tx: mpsc::UnboundedSender<Req>,
+    interceptors: Interceptors<Req, Res>,
+}
+
+/// Caller waiting for the response to a request.
+enum Pending<Res> {
+    Response(oneshot::Sender<Result<Res>>),
+    // NOTE: Unbounded, so that a slow consumer of one stream doesn't hold up the
+    // responses to every other request.
+    Stream(mpsc::UnboundedSender<Result<Res>>),
+    /// Like `Response`, but also receives the credit granted for uploading.
+    Upload(oneshot::Sender<Result<Res>>, mpsc::UnboundedSender<u32>),
 }
 
 impl<Req, Res> AbstractClient<Req, Res> {
-    pub fn new(transport: ClientTransport<Req, Res>) -> (Self, impl Future<Output = ()>) {
+    /// Create a new client over any `Sink` of requests and `Stream` of responses,
+    /// such as the halves of [`transport`] or a socket.
+    ///
+    /// Returns a future that must be spawned on a runtime. It forwards requests to
+    /// the sink and dispatches responses to their callers. Requests wait for the
+    /// sink to be ready, so a slow transport applies backpressure to the caller.
+    /// The future completes once the transport closes, failing the requests that
+    /// are still waiting with [`RequestError::TransportClosed`].
+    pub fn new<Tx, Rx>(transport: (Tx, Rx)) -> (Self, impl Future<Output = ()>)
+    where
+        Tx: Sink<Envelope<Req>>,
+        Rx: Stream<Item = Envelope<Result<Res>>>,
+    {
+        Self::with_requests(transport, |requests| requests)
+    }
+
+    /// Like [`new`](Self::new), but requests made in quick succession are sent
+    /// to the server in a single [`Envelope::Batch`]. A batch is started by the
+    /// first request and collects more requests until the future returned by
+    /// `window` completes.
+    ///
+    /// ```rust,ignore
+    /// let window = || tokio::time::sleep(Duration::from_millis(5));
+    /// let (client, client_task) = AbstractClient::with_batching(transport, window);
+    /// ```
+    pub fn with_batching<Tx, Rx, F>(
+        transport: (Tx, Rx),
+        window: impl Fn() -> F,
+    ) -> (Self, impl Future<Output = ()>)
+    where
+        Tx: Sink<Envelope<Req>>,
+        Rx: Stream<Item = Envelope<Result<Res>>>,
+        F: Future<Output = ()>,
+    {
+        Self::with_requests(transport, |requests| batch_packets(requests, window))
+    }
+
+    /// Create a client whose requests are passed through `map_requests` before
+    /// being forwarded to the server.
+    fn with_requests<Tx, Rx, S>(
+        transport: (Tx, Rx),
+        map_requests: impl FnOnce(mpsc::Receiver<Envelope<Req>>) -> S,
+    ) -> (Self, impl Future<Output = ()>)
+    where
+        Tx: Sink<Envelope<Req>>,
+        Rx: Stream<Item = Envelope<Result<Res>>>,
+        S: Stream<Item = Envelope<Req>>,
+    {
         let (server_tx, server_rx) = transport;
+        let (client, outgoing) = Self::unconnected();
+        let requests = client.requests.clone();
+        let interceptors = client.interceptors.clone();
 
-        let requests = Arc::new(DashMap::new());
+        let task = async move {
+            let pong_tx = outgoing.pong_tx.clone();
+            let forward_requests = outgoing
+                .stream(map_requests)
+                .map(|mut envelope| {
+                    intercept_request(&interceptors, &mut envelope);
+                    Ok(envelope)
+                })
+                .forward(server_tx);
+            let dispatch_responses =
+                dispatch_server_responses(server_rx, &requests, &interceptors, &pong_tx);
+            future::select(pin!(forward_requests), pin!(dispatch_responses)).await;
+
+            // No responses are coming anymore, so don't leave callers waiting.
+            fail_pending(&requests, RequestError::TransportClosed);
+        };
+
+        (client, task)
+    }
+
+    /// Create a client along with the receiving ends of everything it sends, for
+    /// the client task to forward to the server.
+    fn unconnected() -> (Self, Outgoing<Req>) {
+        let (requests_tx, requests_rx) = mpsc::channel(0);
+        let (cancel_tx, cancel_rx) = mpsc::unbounded();
+        let (notify_tx, notify_rx) = mpsc::unbounded();
+        let (pong_tx, pong_rx) = mpsc::unbounded();
 
         let client = Self {
             counter: Arc::new(AtomicU32::new(0)),
-            requests: requests.clone(),
-            server_tx,
+            requests: Arc::new(DashMap::new()),
+            server_tx: Arc::new(Mutex::new(BoundedTx(requests_tx))),
+            cancel_tx,
+            notify_tx,
+            interceptors: Default::default(),
         };
-
-        (
-            client,
-            dispatch_server_responses::<Res, Res>(server_rx, requests),
-        )
+        let outgoing = Outgoing {
+            requests: requests_rx,
+            cancels: cancel_rx,
+            notifications: notify_rx,
+            pongs: pong_rx,
+            pong_tx,
+        };
+        (client, outgoing)
     }
 
     pub async fn make_request(&self, data: Req) -> Result<Res> {
         //// Make a request.
-        let id = self.counter.fetch_add(1, Ordering::SeqCst);
         let (tx, rx) = oneshot::channel();
-        self.requests.insert(id, tx);
-        self.server_tx.send(Packet { id, data });
+        self.send_request(data, Pending::Response(tx)).await?;
 
         //// Wait for the response.
         match rx.await {
-            Ok(packet) => packet.data,
+            Ok(res) => res,
             Err(_) => Err(RequestError::TransportClosed),
         }
     }
 
+    /// Make a request that the server responds to with any number of packets,
+    /// followed by an end-of-stream marker.
+    ///
+    /// If the request can't be sent, the stream yields a single
+    /// [`RequestError::TransportClosed`].
+    pub async fn make_stream_request(&self, data: Req) -> ResponseStream<Res> {
+        let (tx, rx) = mpsc::unbounded();
+        let id = match self.send_request(data, Pending::Stream(tx.clone())).await {
+            Ok(id) => Some(id),
+            Err(e) => {
+                tx.unbounded_send(Err(e)).ok();
+                None
+            }
+        };
+        ResponseStream {
+            id,
+            rx,
+            requests: self.requests.clone(),
+            cancel_tx: self.cancel_tx.clone(),
+        }
+    }
+
+    /// Make a request along with a stream of `chunks`, which the server handler
+    /// reads as an [`UploadStream`].
+    ///
+    /// Chunks are only sent as fast as the handler consumes them, so `chunks` is
+    /// never buffered as a whole. Uploading stops once the response arrives.
+    pub async fn make_upload_request(
+        &self,
+        data: Req,
+        chunks: impl Stream<Item = Req>,
+    ) -> Result<Res> {
+        //// Make a request.
+        let (tx, rx) = oneshot::channel();
+        let (credit_tx, mut credit_rx) = mpsc::unbounded();
+        let id = self
+            .send_request(data, Pending::Upload(tx, credit_tx))
+            .await?;
+
+        //// Upload the chunks as credit is granted.
+        let upload = async {
+            let mut chunks = pin!(chunks);
+            while let Some(credit) = credit_rx.next().await {
+                for _ in 0..credit {
+                    let envelope = match chunks.next().await {
+                        Some(data) => Envelope::Chunk(Packet::new(id, data)),
+                        None => {
+                            self.server_tx
+                                .lock()
+                                .await
+                                .send(Envelope::End(id))
+                                .await
+                                .ok();
+                            return;
+                        }
+                    };
+                    if self.server_tx.lock().await.send(envelope).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        };
+
+        //// Wait for the response.
+        let response = async {
+            match rx.await {
+                Ok(res) => res,
+                Err(_) => Err(RequestError::TransportClosed),
+            }
+        };
+
+        match future::select(pin!(response), pin!(upload)).await {
+            future::Either::Left((res, _)) => res,
+            future::Either::Right(((), response)) => response.await,
+        }
+    }
+
+    /// Send a request without waiting for it to be sent, nor for a response. The
+    /// server handles it like any other request, but doesn't respond to it.
+    pub fn notify(&self, data: Req) -> Result<()> {
+        self.notify_tx
+            .unbounded_send(data)
+            .map_err(|_| RequestError::TransportClosed)
+    }
+
+    /// Add an interceptor that sees every envelope this client, and all of its
+    /// clones, sends and receives from now on. Interceptors are called in the
+    /// order they were added.
+    pub fn intercept(&self, interceptor: impl Interceptor<Req, Res> + 'static) {
+        self.interceptors
+            .write()
+            .unwrap()
+            .push(Box::new(interceptor));
+    }
+
+    /// Start a batch of requests, which are sent to the server all at once.
+    pub fn batch(&self) -> Batch<'_, Req, Res> {
+        Batch::new(self)
+    }
+
+    /// Register the caller waiting for the response and send the request.
+    async fn send_request(&self, data: Req, pending: Pending<Res>) -> Result<u32> {
+        let id = self.next_id();
+        self.requests.insert(id, pending);
+        let packet = Packet::new(id, data);
+        if let Err(e) = self.send(packet.into()).await {
+            self.requests.remove(&id);
+            return Err(e);
+        }
+        Ok(id)
+    }
+
+    fn next_id(&self) -> u32 {
+        self.counter.fetch_add(1, Ordering::SeqCst)
+    }
+
+    async fn send(&self, envelope: Envelope<Req>) -> Result<()> {
+        let sent = self.server_tx.lock().await.send(envelope).await;
+        sent.map_err(|_| RequestError::TransportClosed)
+    }
+
     pub fn cancel_all(&self) {
-        // NOTE: We can't do into_iter() because DashMap is behind Arc.
+        fail_pending(&self.requests, RequestError::Cancelled);
+    }
+}
 
-        // Collect all requests keys
-        let keys: Vec<u32> = self.requests.iter().map(|entry| *entry.key()).collect();
+/// Receiving ends of the channels an [`AbstractClient`] sends into.
+struct Outgoing<Req> {
+    requests: mpsc::Receiver<Envelope<Req>>,
+    cancels: mpsc::UnboundedReceiver<u32>,
+    notifications: mpsc::UnboundedReceiver<Req>,
+    pongs: mpsc::UnboundedReceiver<()>,
+    /// Answers pings of the server.
+    pong_tx: mpsc::UnboundedSender<()>,
+}
 
-        // Cancel all requests
-        for key in keys {
-            if let Some((_, sender)) = self.requests.remove(&key) {
-                let packet = Packet {
-                    id: key,
-                    data: Err(RequestError::Cancelled),
-                };
-                sender.send(packet).ok();
+impl<Req> Outgoing<Req> {
+    /// Everything to send to the server, with requests passed through
+    /// `map_requests`. Ends once the client and all of its clones are dropped.
+    fn stream<S: Stream<Item = Envelope<Req>>>(
+        self,
+        map_requests: impl FnOnce(mpsc::Receiver<Envelope<Req>>) -> S,
+    ) -> impl Stream<Item = Envelope<Req>> {
+        let cancels = self.cancels.map(Envelope::Cancel);
+        let notifications = self
+            .notifications
+            .map(|data| Envelope::Notification(data, Meta::new()));
+        let unbounded = stream::select(cancels, notifications);
+        let pongs = self.pongs.map(|()| Envelope::Pong);
+        // The client task holds a sender of pongs itself, so they never end.
+        until_end(
+            stream::select(map_requests(self.requests), unbounded),
+            pongs,
+        )
+    }
+}
+
+/// Items of `rx`, merged with the ones of `extra`, ending with `rx`.
+fn until_end<T>(rx: impl Stream<Item = T>, extra: impl Stream<Item = T>) -> impl Stream<Item = T> {
+    let rx = rx.map(Some).chain(stream::once(future::ready(None)));
+    stream::select(rx, extra.map(Some))
+        .take_while(|item| future::ready(item.is_some()))
+        .filter_map(future::ready)
+}
+
+/// Fail every request that is still waiting for a response with `error`.
+fn fail_pending<Res>(requests: &DashMap<u32, Pending<Res>>, error: RequestError) {
+    fail_pending_unless(requests, error, |_, _| false)
+}
+
+/// Like [`fail_pending`], but leaves the requests `keep` returns `true` for
+/// waiting.
+fn fail_pending_unless<Res>(
+    requests: &DashMap<u32, Pending<Res>>,
+    error: RequestError,
+    keep: impl Fn(u32, &Pending<Res>) -> bool,
+) {
+    // NOTE: We can't do into_iter() because DashMap is behind Arc.
+
+    // Collect all requests keys
+    let keys: Vec<u32> = requests
+        .iter()
+        .filter(|entry| !keep(*entry.key(), entry.value()))
+        .map(|entry| *entry.key())
+        .collect();
+
+    // Fail all requests
+    for key in keys {
+        match requests.remove(&key) {
+            Some((_, Pending::Response(sender) | Pending::Upload(sender, _))) => {
+                sender.send(Err(error.clone())).ok();
+            }
+            Some((_, Pending::Stream(sender))) => {
+                sender.unbounded_send(Err(error.clone())).ok();
             }
+            None => {}
         }
     }
 }
@@ -109,40 +473,339 @@ impl<Req, Res> Clone for AbstractClient<Req, Res> {
             counter: self.counter.clone(),
             server_tx: self.server_tx.clone(),
             requests: self.requests.clone(),
+            cancel_tx: self.cancel_tx.clone(),
+            notify_tx: self.notify_tx.clone(),
+            interceptors: self.interceptors.clone(),
         }
     }
 }
 
 async fn dispatch_server_responses<Req, Res>(
-    mut server_rx: Rx<Packet<Result<Res>>>,
-    requests: Arc<DashMap<u32, oneshot::Sender<Packet<Result<Res>>>>>,
+    server_rx: impl Stream<Item = Envelope<Result<Res>>>,
+    requests: &DashMap<u32, Pending<Res>>,
+    interceptors: &Interceptors<Req, Res>,
+    pong_tx: &mpsc::UnboundedSender<()>,
 ) {
-    while let Some(res) = server_rx.recv().await {
-        if let Some((_, sender)) = requests.remove(&res.id) {
-            let packet = Packet {
-                id: res.id,
-                data: res.data,
-            };
-            sender.send(packet).ok();
+    let mut server_rx = pin!(server_rx);
+    while let Some(mut envelope) = server_rx.next().await {
+        intercept_response(interceptors, &mut envelope);
+        match envelope {
+            Envelope::Packet(res) => dispatch_response(requests, res),
+            Envelope::Batch(responses) => {
+                for res in responses {
+                    dispatch_response(requests, res);
+                }
+            }
+            // Dropping the sender ends the stream.
+            Envelope::End(id) => _ = requests.remove(&id),
+            Envelope::Credit(id, credit) => {
+                if let Some(Pending::Upload(_, credit_tx)) = requests.get(&id).as_deref() {
+                    credit_tx.unbounded_send(credit).ok();
+                }
+            }
+            Envelope::Ping => _ = pong_tx.unbounded_send(()),
+            // Only clients upload chunks, cancel requests and notify. Pongs and
+            // hellos only matter to a `Heartbeat` and a `Handshake`, which see
+            // them before the client does.
+            Envelope::Chunk(_) | Envelope::Cancel(_) | Envelope::Notification(..) => {}
+            Envelope::Pong | Envelope::Hello(_) => {}
         }
     }
 }
 
+fn dispatch_response<Res>(requests: &DashMap<u32, Pending<Res>>, res: Packet<Result<Res>>) {
+    let Entry::Occupied(entry) = requests.entry(res.id) else {
+        return;
+    };
+    if let Pending::Stream(sender) = entry.get() {
+        sender.unbounded_send(res.data).ok();
+    } else if let Pending::Response(sender) | Pending::Upload(sender, _) = entry.remove() {
+        sender.send(res.data).ok();
+    }
+}
+
+/// A request as handed to the request handler of [`AbstractServer`].
+pub struct Request<Req> {
+    /// `None` for notifications, which can't be responded to.
+    pub id: Option<u32>,
+    pub data: Req,
+    /// Chunks uploaded along with the request, if any.
+    pub uploads: UploadStream<Req>,
+    /// Where the request came from, its metadata, and whether it was cancelled.
+    pub context: Context,
+}
+
+/// Request that is being handled by an [`AbstractServer`].
+struct Running<Req> {
+    abort: AbortHandle,
+    /// Tells the [`Context`] of the request that it was cancelled.
+    cancel: oneshot::Sender<()>,
+    chunks: mpsc::UnboundedSender<Req>,
+}
+
 pub struct AbstractServer;
 
 impl AbstractServer {
-    pub async fn new<Req, Res>(
-        server_transport: ServerTransport<Req, Res>,
-        handle_request: impl AsyncFn(Req) -> Result<Res>,
-    ) {
+    /// Serve requests coming from any `Stream` of requests, and send the responses
+    /// into a `Sink`. Every request is handed to `handle_request` along with the
+    /// [`Responder`] to respond with. The future completes when the request stream
+    /// ends.
+    ///
+    /// All handlers run within this future, so neither they nor the future have
+    /// to be `Send`, and it can run on a single-threaded runtime. To handle
+    /// requests in parallel instead, hand them to [`Spawned`].
+    pub async fn new<Req, Res, Rx, Tx>(
+        server_transport: (Rx, Tx),
+        handle_request: impl AsyncFn(Request<Req>, Responder<Res>),
+    ) where
+        Rx: Stream<Item = Envelope<Req>>,
+        Tx: Sink<Envelope<Result<Res>>>,
+    {
+        Self::with_shutdown(server_transport, ShutdownHandle::new(), handle_request).await
+    }
+
+    /// Like [`new`](AbstractServer::new), but can be shut down gracefully with
+    /// `shutdown`. The future then completes once the last response is sent,
+    /// even if the request stream hasn't ended.
+    pub async fn with_shutdown<Req, Res, Rx, Tx>(
+        server_transport: (Rx, Tx),
+        shutdown: ShutdownHandle,
+        handle_request: impl AsyncFn(Request<Req>, Responder<Res>),
+    ) where
+        Rx: Stream<Item = Envelope<Req>>,
+        Tx: Sink<Envelope<Result<Res>>>,
+    {
+        let connection = Default::default();
+        Self::serve_connection(server_transport, connection, shutdown, handle_request).await
+    }
+
+    /// Like [`with_shutdown`](AbstractServer::with_shutdown), with what is known
+    /// about the other end of the transport passed on in the [`Context`] of every
+    /// request.
+    async fn serve_connection<Req, Res, Rx, Tx>(
+        server_transport: (Rx, Tx),
+        connection: Arc<Connection>,
+        shutdown: ShutdownHandle,
+        handle_request: impl AsyncFn(Request<Req>, Responder<Res>),
+    ) where
+        Rx: Stream<Item = Envelope<Req>>,
+        Tx: Sink<Envelope<Result<Res>>>,
+    {
+        let _running = shutdown.running();
+        let shutdown = &shutdown;
         let (client_rx, client_tx) = server_transport;
-        let handle_request = async |req: Packet<Req>| {
-            let data = handle_request(req.data).await;
-            let res = Packet { id: req.id, data };
-            client_tx.send(res);
+        let (responses_tx, responses_rx) = mpsc::channel(0);
+        let (credit_tx, credit_rx) = mpsc::unbounded();
+
+        let handle_requests = async move {
+            // Requests that are being handled.
+            let running = DashMap::new();
+            // Requests of a batch respond to `batch_tx`, see `Responder::batched`.
+            let handle_packet = async |Packet { id, data, meta }, batch_tx: Option<_>| {
+                let (chunks_tx, chunks_rx) = mpsc::unbounded();
+                let (cancel_tx, cancel_rx) = oneshot::channel();
+                let context = Context::new(connection.clone(), meta, cancel_rx);
+                let req = Request {
+                    id: Some(id),
+                    data,
+                    uploads: UploadStream::new(id, chunks_rx, credit_tx.clone()),
+                    context: context.clone(),
+                };
+                let responder = match batch_tx {
+                    Some(batch_tx) => Responder::batched(id, batch_tx, responses_tx.clone()),
+                    None => Responder::new(id, responses_tx.clone()),
+                };
+                let handler = context.scope(handle_request(req, responder));
+                let (handler, abort) = future::abortable(handler);
+                running.insert(
+                    id,
+                    Running {
+                        abort,
+                        cancel: cancel_tx,
+                        chunks: chunks_tx,
+                    },
+                );
+                handler.await.ok();
+                running.remove(&id);
+            };
+            let handle_envelope = async |envelope| match envelope {
+                Envelope::Packet(packet) => handle_packet(packet, None).await,
+                Envelope::Batch(packets) => {
+                    // Collect the single responses of the whole batch, and send
+                    // them as a batch too. Response streams are sent on their own.
+                    let (batch_tx, batch_rx) = mpsc::channel(0);
+                    let handlers = packets
+                        .into_iter()
+                        .map(|packet| handle_packet(packet, Some(batch_tx.clone())));
+                    let handlers = future::join_all(handlers);
+                    drop(batch_tx);
+                    // Every responder of the batch has responded once this ends,
+                    // even if some of the handlers are still streaming.
+                    let send_batch = async {
+                        let responses: Vec<_> = batch_rx
+                            .filter_map(|envelope| match envelope {
+                                Envelope::Packet(packet) => future::ready(Some(packet)),
+                                _ => future::ready(None),
+                            })
+                            .collect()
+                            .await;
+                        if !responses.is_empty() {
+                            let batch = Envelope::Batch(responses);
+                            responses_tx.clone().send(batch).await.ok();
+                        }
+                    };
+                    future::join(handlers, send_batch).await;
+                }
+                Envelope::Chunk(Packet { id, data, .. }) => {
+                    if let Some(running) = running.get(&id) {
+                        running.chunks.unbounded_send(data).ok();
+                    }
+                }
+                Envelope::End(id) => {
+                    if let Some(running) = running.get(&id) {
+                        running.chunks.close_channel();
+                    }
+                }
+                Envelope::Cancel(id) => {
+                    if let Some((_, running)) = running.remove(&id) {
+                        running.cancel.send(()).ok();
+                        running.abort.abort();
+                    }
+                }
+                Envelope::Notification(data, meta) => {
+                    // Notifications can't be cancelled.
+                    let (_, cancel_rx) = oneshot::channel();
+                    let context = Context::new(connection.clone(), meta, cancel_rx);
+                    let req = Request {
+                        id: None,
+                        data,
+                        uploads: UploadStream::empty(),
+                        context: context.clone(),
+                    };
+                    let handler = handle_request(req, Responder::notification());
+                    context.scope(handler).await;
+                }
+                Envelope::Ping => _ = responses_tx.clone().send(Envelope::Pong).await,
+                // Only servers grant credit. Pongs and hellos only matter to a
+                // `Heartbeat` and a `Handshake`, which see them before the server
+                // does.
+                Envelope::Credit(..) | Envelope::Pong | Envelope::Hello(_) => {}
+            };
+            let client_rx = client_rx.take_until(shutdown.stopped());
+            client_rx.for_each_concurrent(None, handle_envelope).await;
+        };
+        // Past the deadline of a shutdown, dropping the handlers makes their
+        // responders respond with `Cancelled`.
+        let handle_requests = async {
+            future::select(pin!(handle_requests), pin!(shutdown.aborted())).await;
+        };
+
+        let send_responses = async move {
+            let mut client_tx = pin!(client_tx);
+            let credits = credit_rx.map(|(id, credit)| Envelope::Credit(id, credit));
+            let mut responses_rx = stream::select(responses_rx, credits);
+            while let Some(res) = responses_rx.next().await {
+                // If the client is gone, there is no one left to respond to.
+                client_tx.send(res).await.ok();
+            }
+        };
+
+        future::join(handle_requests, send_responses).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn test_notification_is_not_responded_to() {
+        let ((tx, mut rx), server_transport) = transport();
+
+        let (notified_tx, mut notified_rx) = mpsc::unbounded();
+        let handle_request = async |req: Request<u32>, responder: Responder<u32>| {
+            if req.id.is_none() {
+                notified_tx.unbounded_send(req.data).unwrap();
+            }
+            responder.respond(Ok(req.data)).await;
+        };
+        let server_task = AbstractServer::new(server_transport, handle_request);
+
+        let test = async {
+            tx.send(Envelope::Notification(1, Meta::new()));
+            assert_eq!(notified_rx.next().await, Some(1));
+
+            // The first thing the client gets back is the response to a request.
+            tx.send(Envelope::Packet(Packet::new(0, 2)));
+            let Some(Envelope::Packet(res)) = rx.recv().await else {
+                panic!("expected a response");
+            };
+            assert_eq!((res.id, res.data.unwrap()), (0, 2));
         };
-        // TODO: Consider returning a stream, so that user can handle requests in
-        // parallel if they want to.
-        client_rx.0.for_each_concurrent(None, handle_request).await;
+
+        block_on(future::select(pin!(test), pin!(server_task)));
+    }
+
+    #[test]
+    fn test_requests_fail_when_transport_closes() {
+        let (client_transport, (mut rx, tx)) = transport();
+        let (client, client_task) = AbstractClient::<u32, u32>::new(client_transport);
+
+        let test = async {
+            let close = async {
+                // The server goes away without responding.
+                rx.recv().await;
+                drop((rx, tx));
+            };
+            let (res, _) = future::join(client.make_request(1), close).await;
+            assert!(matches!(res, Err(RequestError::TransportClosed)));
+        };
+
+        block_on(future::join(test, client_task));
+    }
+
+    /// Sets the same locale on every request.
+    struct Locale;
+
+    impl Interceptor<u32, String> for Locale {
+        fn request(&self, envelope: &mut Envelope<u32>) {
+            if let Envelope::Packet(packet) = envelope {
+                packet.meta.insert("locale".to_string(), "de".to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn test_interceptor_sets_meta() {
+        let (client_transport, server_transport) = transport();
+        let (client, client_task) = AbstractClient::new(client_transport);
+        client.intercept(Locale);
+
+        let handle_request = async |req: Request<u32>, responder: Responder<String>| {
+            let locale = req
+                .context
+                .meta()
+                .get("locale")
+                .cloned()
+                .unwrap_or_default();
+            responder.respond(Ok(locale)).await;
+        };
+        let server_task = AbstractServer::new(server_transport, handle_request);
+
+        let test = async {
+            assert_eq!(client.make_request(0).await.unwrap(), "de");
+
+            // Requests of a batch are intercepted one by one.
+            let mut batch = client.batch();
+            let responses = [batch.request(1), batch.request(2)];
+            batch.send().await.unwrap();
+            let responses = future::try_join_all(responses).await.unwrap();
+            assert_eq!(responses, ["de", "de"]);
+        };
+
+        let tasks = future::join(client_task, server_task);
+        block_on(future::select(pin!(test), pin!(tasks)));
     }
 }
@@ -0,0 +1,216 @@
+use std::{mem, pin::pin};
+
+use futures::{
+    Stream, StreamExt,
+    channel::oneshot,
+    future::{self, Either},
+    stream,
+};
+
+use super::{AbstractClient, Envelope, Packet, Pending, RequestError, Result};
+
+/// Requests that are sent to the server in a single [`Envelope::Batch`], created
+/// with [`AbstractClient::batch`].
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let mut batch = client.batch();
+/// let hello = batch.request(TestRequest::say_hello(("World".to_string(),)));
+/// let ping = batch.request(TestRequest::ping_enum((EnumAdjacentlyTagged::VariantA,)));
+/// batch.send().await?;
+///
+/// let (hello, ping) = futures::join!(hello, ping);
+/// ```
+pub struct Batch<'a, Req, Res> {
+    client: &'a AbstractClient<Req, Res>,
+    packets: Vec<Packet<Req>>,
+    senders: Vec<(u32, oneshot::Sender<Result<Res>>)>,
+}
+
+impl<'a, Req, Res> Batch<'a, Req, Res> {
+    pub(super) fn new(client: &'a AbstractClient<Req, Res>) -> Self {
+        Batch {
+            client,
+            packets: Vec::new(),
+            senders: Vec::new(),
+        }
+    }
+
+    /// Add a request to the batch. The returned future resolves to its response
+    /// once the batch has been sent, or to [`RequestError::Cancelled`] if the batch
+    /// is dropped without being sent.
+    pub fn request(&mut self, data: Req) -> impl Future<Output = Result<Res>> + use<Req, Res> {
+        let id = self.client.next_id();
+        let (tx, rx) = oneshot::channel();
+        self.packets.push(Packet::new(id, data));
+        self.senders.push((id, tx));
+        async move {
+            match rx.await {
+                Ok(res) => res,
+                Err(_) => Err(RequestError::TransportClosed),
+            }
+        }
+    }
+
+    /// Send all requests of the batch at once.
+    pub async fn send(mut self) -> Result<()> {
+        if self.packets.is_empty() {
+            return Ok(());
+        }
+
+        for (id, tx) in self.senders.drain(..) {
+            self.client.requests.insert(id, Pending::Response(tx));
+        }
+        let packets = mem::take(&mut self.packets);
+        let ids: Vec<_> = packets.iter().map(|packet| packet.id).collect();
+        let sent = self.client.send(Envelope::Batch(packets)).await;
+        if sent.is_err() {
+            // Dropping the senders fails the requests with `TransportClosed`.
+            for id in ids {
+                self.client.requests.remove(&id);
+            }
+        }
+        sent
+    }
+}
+
+impl<Req, Res> Drop for Batch<'_, Req, Res> {
+    fn drop(&mut self) {
+        // Only still there if the batch wasn't sent.
+        for (_, tx) in self.senders.drain(..) {
+            tx.send(Err(RequestError::Cancelled)).ok();
+        }
+    }
+}
+
+/// Collect the request packets of `envelopes` into batches. A batch is started by
+/// the first packet and collects every packet that follows until `window`
+/// completes. Any other envelope ends the batch early, so that envelopes keep
+/// their order.
+pub(super) fn batch_packets<Req, F: Future<Output = ()>>(
+    envelopes: impl Stream<Item = Envelope<Req>> + Unpin,
+    window: impl Fn() -> F,
+) -> impl Stream<Item = Envelope<Req>> {
+    let state = (envelopes, window, None);
+    stream::unfold(state, async |(mut envelopes, window, next)| {
+        let first = match next {
+            Some(envelope) => envelope,
+            None => envelopes.next().await?,
+        };
+        let Envelope::Packet(first) = first else {
+            return Some((first, (envelopes, window, None)));
+        };
+
+        let mut packets = vec![first];
+        let mut next = None;
+        let mut timeout = pin!(window());
+        loop {
+            match future::select(timeout.as_mut(), envelopes.next()).await {
+                Either::Right((Some(Envelope::Packet(packet)), _)) => packets.push(packet),
+                Either::Right((envelope, _)) => {
+                    next = envelope;
+                    break;
+                }
+                Either::Left(_) => break,
+            }
+        }
+
+        let batch = match packets.len() {
+            1 => Envelope::Packet(packets.pop().unwrap()),
+            _ => Envelope::Batch(packets),
+        };
+        Some((batch, (envelopes, window, next)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::{AbstractServer, Request, Responder, transport};
+
+    #[test]
+    fn test_batch_packets_keeps_order() {
+        let envelopes = stream::iter([
+            Envelope::Packet(Packet::new(0, ())),
+            Envelope::Packet(Packet::new(1, ())),
+            Envelope::Cancel(0),
+            Envelope::Packet(Packet::new(2, ())),
+        ]);
+        // The window never closes, so only other envelopes end a batch.
+        let batched = batch_packets(envelopes, future::pending::<()>);
+        let batched: Vec<_> = block_on(batched.collect());
+
+        let [
+            Envelope::Batch(batch),
+            Envelope::Cancel(0),
+            Envelope::Packet(last),
+        ] = &batched[..]
+        else {
+            panic!("unexpected envelopes");
+        };
+        let ids: Vec<_> = batch.iter().map(|packet| packet.id).collect();
+        assert_eq!(ids, [0, 1]);
+        assert_eq!(last.id, 2);
+    }
+
+    #[test]
+    fn test_batch_is_responded_to() {
+        let (client_transport, server_transport) = transport();
+        let (client, client_task) = AbstractClient::new(client_transport);
+
+        let handle_request = async |req: Request<u32>, responder: Responder<u32>| {
+            responder.respond(Ok(req.data * 2)).await;
+        };
+        let server_task = AbstractServer::new(server_transport, handle_request);
+
+        let test = async {
+            let mut batch = client.batch();
+            let responses: Vec<_> = (0..3).map(|i| batch.request(i)).collect();
+            batch.send().await.unwrap();
+            let responses = future::try_join_all(responses).await.unwrap();
+            assert_eq!(responses, [0, 2, 4]);
+
+            // Requests of a batch that is never sent are cancelled.
+            let mut batch = client.batch();
+            let response = batch.request(0);
+            drop(batch);
+            assert!(matches!(response.await, Err(RequestError::Cancelled)));
+        };
+
+        let tasks = future::join(client_task, server_task);
+        block_on(future::select(pin!(test), pin!(tasks)));
+    }
+
+    #[test]
+    fn test_stream_does_not_hold_up_batch() {
+        let ((tx, mut rx), server_transport) = transport();
+
+        let handle_request = async |req: Request<u32>, responder: Responder<u32>| match req.data {
+            0 => responder.respond_stream(stream::iter(0..).map(Ok)).await,
+            data => responder.respond(Ok(data)).await,
+        };
+        let server_task = AbstractServer::new(server_transport, handle_request);
+
+        let test = async {
+            tx.send(Envelope::Batch(vec![Packet::new(0, 0), Packet::new(1, 1)]));
+
+            // The stream never ends, but the rest of the batch is responded to.
+            loop {
+                match rx.recv().await {
+                    Some(Envelope::Packet(item)) => assert_eq!(item.id, 0),
+                    Some(Envelope::Batch(responses)) => {
+                        let ids: Vec<_> = responses.iter().map(|packet| packet.id).collect();
+                        assert_eq!(ids, [1]);
+                        break;
+                    }
+                    _ => panic!("unexpected envelope"),
+                }
+            }
+        };
+
+        block_on(future::select(pin!(test), pin!(server_task)));
+    }
+}
@@ -1,22 +1,99 @@
-use std::ops::{Deref, DerefMut};
+use std::{
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    task::{Context, Poll},
+};
 
 use futures::{
-    StreamExt,
-    channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    Sink, SinkExt, Stream, StreamExt,
+    channel::mpsc::{
+        self, Receiver as BoundedReceiver, Sender as BoundedSender, UnboundedReceiver,
+        UnboundedSender,
+    },
 };
 
+use super::TransportError;
+
+#[allow(clippy::type_complexity)]
 pub fn transport<Req, Res>() -> ((Tx<Req>, Rx<Res>), (Rx<Req>, Tx<Res>)) {
     let (req_tx, req_rx) = mpsc::unbounded();
     let (res_tx, res_rx) = mpsc::unbounded();
     ((Tx(req_tx), Rx(res_rx)), (Rx(req_rx), Tx(res_tx)))
 }
 
+/// In-memory transport between two [`AbstractPeer`](super::AbstractPeer)s.
+/// Unlike [`transport`], both halves are a `(Sink, Stream)` pair.
+#[allow(clippy::type_complexity)]
+pub fn peer_transport<A, B>() -> ((Tx<A>, Rx<B>), (Tx<B>, Rx<A>)) {
+    let (a_tx, a_rx) = mpsc::unbounded();
+    let (b_tx, b_rx) = mpsc::unbounded();
+    ((Tx(a_tx), Rx(b_rx)), (Tx(b_tx), Rx(a_rx)))
+}
+
+/// Like [`transport`], but both directions hold at most `capacity` messages
+/// (plus one slot per sender, see [`mpsc::channel`]). Once full, senders wait
+/// in [`BoundedTx::send`] until the receiving side catches up.
+///
+/// Both halves are a [`Sink`] and a [`Stream`], so they can be passed to
+/// [`AbstractClient`](super::AbstractClient) and
+/// [`AbstractServer`](super::AbstractServer) like any other transport.
+#[allow(clippy::type_complexity)]
+pub fn transport_bounded<Req, Res>(
+    capacity: usize,
+) -> (
+    (BoundedTx<Req>, BoundedRx<Res>),
+    (BoundedRx<Req>, BoundedTx<Res>),
+) {
+    let (req_tx, req_rx) = mpsc::channel(capacity);
+    let (res_tx, res_rx) = mpsc::channel(capacity);
+    (
+        (BoundedTx(req_tx), BoundedRx(res_rx)),
+        (BoundedRx(req_rx), BoundedTx(res_tx)),
+    )
+}
+
+//// Unbounded
+
 pub struct Tx<T>(pub UnboundedSender<T>);
 
 impl<T> Tx<T> {
     pub fn send(&self, message: T) {
         self.0.unbounded_send(message).unwrap();
     }
+
+    pub fn try_send(&self, message: T) -> Result<(), TransportError> {
+        self.0
+            .unbounded_send(message)
+            .map_err(|_| TransportError::Closed)
+    }
+}
+
+impl<T> Sink<T> for Tx<T> {
+    type Error = TransportError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0
+            .poll_ready_unpin(cx)
+            .map_err(|_| TransportError::Closed)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        self.0
+            .start_send_unpin(item)
+            .map_err(|_| TransportError::Closed)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0
+            .poll_flush_unpin(cx)
+            .map_err(|_| TransportError::Closed)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0
+            .poll_close_unpin(cx)
+            .map_err(|_| TransportError::Closed)
+    }
 }
 
 impl<T> Clone for Tx<T> {
@@ -51,6 +128,14 @@ impl<T> Rx<T> {
     }
 }
 
+impl<T> Stream for Rx<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
+
 impl<T> Deref for Rx<T> {
     type Target = UnboundedReceiver<T>;
 
@@ -64,3 +149,132 @@ impl<T> DerefMut for Rx<T> {
         &mut self.0
     }
 }
+
+//// Bounded
+
+/// NOTE: Every clone of a [`BoundedTx`] gets its own guaranteed slot in the
+/// channel (see [`mpsc::channel`]), so the buffer grows by one for each clone.
+pub struct BoundedTx<T>(pub BoundedSender<T>);
+
+impl<T> BoundedTx<T> {
+    /// Sends a message, waiting until the channel has capacity for it.
+    pub async fn send(&mut self, message: T) -> Result<(), TransportError> {
+        SinkExt::send(&mut self.0, message)
+            .await
+            .map_err(|_| TransportError::Closed)
+    }
+
+    /// Sends a message without waiting. Fails with [`TransportError::Full`] if
+    /// the channel is at capacity.
+    pub fn try_send(&mut self, message: T) -> Result<(), TransportError> {
+        self.0.try_send(message).map_err(|e| match e.is_full() {
+            true => TransportError::Full,
+            false => TransportError::Closed,
+        })
+    }
+}
+
+impl<T> Sink<T> for BoundedTx<T> {
+    type Error = TransportError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0
+            .poll_ready_unpin(cx)
+            .map_err(|_| TransportError::Closed)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        self.0
+            .start_send_unpin(item)
+            .map_err(|_| TransportError::Closed)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0
+            .poll_flush_unpin(cx)
+            .map_err(|_| TransportError::Closed)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0
+            .poll_close_unpin(cx)
+            .map_err(|_| TransportError::Closed)
+    }
+}
+
+impl<T> Clone for BoundedTx<T> {
+    fn clone(&self) -> Self {
+        BoundedTx(self.0.clone())
+    }
+}
+
+impl<T> Deref for BoundedTx<T> {
+    type Target = BoundedSender<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for BoundedTx<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+pub struct BoundedRx<T>(pub BoundedReceiver<T>);
+
+impl<T> BoundedRx<T> {
+    pub fn try_recv(&mut self) -> Option<T> {
+        self.0.try_next().ok().flatten()
+    }
+
+    pub async fn recv(&mut self) -> Option<T> {
+        self.0.next().await
+    }
+}
+
+impl<T> Stream for BoundedRx<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
+
+impl<T> Deref for BoundedRx<T> {
+    type Target = BoundedReceiver<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for BoundedRx<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn test_bounded_try_send_reports_full() {
+        let ((mut tx, _), (mut rx, _)) = transport_bounded::<u32, ()>(1);
+
+        // One slot from `capacity` and one guaranteed slot for the sender.
+        tx.try_send(0).unwrap();
+        tx.try_send(1).unwrap();
+        assert!(matches!(tx.try_send(2), Err(TransportError::Full)));
+
+        assert_eq!(block_on(rx.recv()), Some(0));
+        tx.try_send(2).unwrap();
+
+        drop(rx);
+        assert!(matches!(tx.try_send(3), Err(TransportError::Closed)));
+    }
+}
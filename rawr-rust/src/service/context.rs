@@ -0,0 +1,214 @@
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::HashMap,
+    net::SocketAddr,
+    pin::pin,
+    sync::Arc,
+};
+
+use futures::{
+    FutureExt,
+    channel::oneshot,
+    future::{self, Shared},
+};
+
+use super::{ConnectionId, Meta};
+
+/// Everything a request handler may want to know about a request, apart from
+/// its data: where it came from, the metadata sent along with it, and whether
+/// the client is still waiting for it.
+///
+/// Handlers that get a [`Request`](super::Request) find it in
+/// [`Request::context`](super::Request::context). Generated service traits don't
+/// take it as an argument, so their implementations use [`Context::current`]
+/// instead.
+///
+/// Cloning a context is cheap.
+#[derive(Clone)]
+pub struct Context(Arc<ContextInner>);
+
+struct ContextInner {
+    connection: Arc<Connection>,
+    meta: Meta,
+    cancelled: Shared<oneshot::Receiver<()>>,
+}
+
+impl Context {
+    pub(super) fn new(
+        connection: Arc<Connection>,
+        meta: Meta,
+        cancelled: oneshot::Receiver<()>,
+    ) -> Self {
+        Context(Arc::new(ContextInner {
+            connection,
+            meta,
+            cancelled: cancelled.shared(),
+        }))
+    }
+
+    /// Context of the request whose handler is currently running, if any.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,ignore
+    /// impl TestService for ServiceImpl {
+    ///     async fn say_hello(&self, arg: String) -> String {
+    ///         let user = Context::current().and_then(|ctx| ctx.get::<User>().cloned());
+    ///         format!("Hello, {}! (asked by {:?})", arg, user)
+    ///     }
+    /// }
+    /// ```
+    pub fn current() -> Option<Context> {
+        CURRENT.with_borrow(|current| current.clone())
+    }
+
+    /// Connection the request came in on, if served by a [`Server`](super::Server).
+    pub fn connection(&self) -> Option<ConnectionId> {
+        self.0.connection.id
+    }
+
+    /// Address of the client, if the transport has one and it was passed to the
+    /// server with [`Accepted::peer_addr`](super::Accepted::peer_addr).
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.0.connection.peer_addr
+    }
+
+    /// Metadata the client sent along with the request.
+    pub fn meta(&self) -> &Meta {
+        &self.0.meta
+    }
+
+    /// State of type `T` attached to the connection when it was accepted, e.g.
+    /// the authenticated user.
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        self.0.connection.state.get()
+    }
+
+    /// Whether the client has cancelled the request.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self.0.cancelled.peek(), Some(Ok(())))
+    }
+
+    /// Completes once the client has cancelled the request. The handler itself is
+    /// stopped right away, so this is for work it has handed off, like spawned
+    /// tasks.
+    pub async fn cancelled(&self) {
+        // Dropping the sender without sending means the request has finished
+        // without being cancelled.
+        if self.0.cancelled.clone().await.is_err() {
+            future::pending().await
+        }
+    }
+
+    /// Run `future` with this context as the [`current`](Context::current) one.
+    pub(super) async fn scope<F: Future>(self, future: F) -> F::Output {
+        let mut future = pin!(future);
+        future::poll_fn(|cx| {
+            let previous = CURRENT.replace(Some(self.clone()));
+            let poll = future.as_mut().poll(cx);
+            CURRENT.set(previous);
+            poll
+        })
+        .await
+    }
+}
+
+impl Default for Context {
+    /// Context of a request that came out of nowhere, e.g. in tests.
+    fn default() -> Self {
+        let (_, cancelled) = oneshot::channel();
+        Context::new(Default::default(), Meta::new(), cancelled)
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Context>> = const { RefCell::new(None) };
+}
+
+/// What is known about the other end of a connection. Shared by the contexts of
+/// all requests on that connection.
+#[derive(Default)]
+pub(super) struct Connection {
+    pub id: Option<ConnectionId>,
+    pub peer_addr: Option<SocketAddr>,
+    pub state: State,
+}
+
+/// Values attached to a connection, at most one of each type.
+#[derive(Default)]
+pub struct State(HashMap<TypeId, Box<dyn Any + Send + Sync>>);
+
+impl State {
+    /// Attach `value`, replacing any value of the same type.
+    pub fn insert<T: Any + Send + Sync>(&mut self, value: T) {
+        self.0.insert(TypeId::of::<T>(), Box::new(value));
+    }
+
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        self.0.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{StreamExt, channel::mpsc, executor::block_on, stream};
+
+    use super::*;
+    use crate::{AbstractClient, AbstractServer, Accepted, Request, Responder, Server, transport};
+
+    struct User(&'static str);
+
+    #[test]
+    fn test_handler_sees_connection_state() {
+        let (client_transport, server_transport) = transport();
+        let (client, client_task) = AbstractClient::new(client_transport);
+
+        // Generated services don't get the request, only the current context.
+        let handle_request = async |_: Request<()>, responder: Responder<String>| {
+            let ctx = Context::current().unwrap();
+            let user = ctx.get::<User>().unwrap().0;
+            let res = format!("{} on {}", user, ctx.connection().unwrap());
+            responder.respond(Ok(res)).await;
+        };
+        let accepted = Accepted::new(server_transport).with(User("ada"));
+        let server_task = Server::new(handle_request).serve_accepted(stream::iter([accepted]));
+
+        let test = async {
+            let res = client.make_request(()).await.unwrap();
+            assert_eq!(res, "ada on Connection #0");
+            assert!(Context::current().is_none());
+        };
+
+        let tasks = future::join(client_task, server_task);
+        block_on(future::select(pin!(test), pin!(tasks)));
+    }
+
+    #[test]
+    fn test_context_is_cancelled() {
+        let (client_transport, server_transport) = transport();
+        let (client, client_task) = AbstractClient::<(), ()>::new(client_transport);
+
+        // The handler hands its context out and never responds.
+        let (contexts_tx, mut contexts_rx) = mpsc::unbounded();
+        let handle_request = async |req: Request<()>, _responder: Responder<()>| {
+            contexts_tx.unbounded_send(req.context).unwrap();
+            future::pending::<()>().await;
+        };
+        let server_task = AbstractServer::new(server_transport, handle_request);
+
+        let test = async {
+            let responses = client.make_stream_request(()).await;
+            let ctx = contexts_rx.next().await.unwrap();
+            assert!(!ctx.is_cancelled());
+
+            // Dropping the response stream cancels the request.
+            drop(responses);
+            ctx.cancelled().await;
+            assert!(ctx.is_cancelled());
+        };
+
+        let tasks = future::join(client_task, server_task);
+        block_on(future::select(pin!(test), pin!(tasks)));
+    }
+}
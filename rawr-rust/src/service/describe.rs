@@ -0,0 +1,340 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    EnumDef, EnumRepr, FieldDef, Fingerprint, Schema, SchemaDef, SchemaPtr, Shape, StructDef,
+};
+
+/// Name of the method every generated server answers with the
+/// [`ServiceDescription`] of its service.
+///
+/// Only the generated `handle_request` answers it: a handler given directly to
+/// an [`AbstractServer`](super::AbstractServer) or [`Server`](super::Server)
+/// has to route it there itself, or its clients get no response.
+pub const DESCRIBE_METHOD: &str = "rawr.describe";
+
+/// Definition of a service, written by the code generator next to its client
+/// and server.
+#[derive(Debug, Clone, Copy)]
+pub struct ServiceDef {
+    pub name: &'static str,
+    pub methods: &'static [MethodDef],
+    /// Requests and responses of the service.
+    pub schema: SchemaPtr,
+    /// [`Fingerprint`] of `schema`.
+    pub fingerprint: Fingerprint,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MethodDef {
+    pub name: &'static str,
+    pub kind: MethodKind,
+    pub params: &'static [FieldDef],
+    pub returns: SchemaPtr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Schema, Serialize, Deserialize)]
+pub enum MethodKind {
+    /// Responds once.
+    Request,
+    /// Responds with a stream of items, each of the type the method returns.
+    Stream,
+    /// The last parameter is uploaded as a stream of items of its type. Responds
+    /// once.
+    Upload,
+    /// Doesn't respond at all.
+    Notification,
+}
+
+/// A service and every type it uses, in a form that can be sent over the wire,
+/// like gRPC server reflection. Every generated server answers
+/// [`DESCRIBE_METHOD`] with the description of its service, so that tools can
+/// find out what a running server offers.
+///
+/// Named types are described once in [`types`](ServiceDescription::types), and
+/// referred to by name and module everywhere else.
+#[derive(Debug, Clone, PartialEq, Schema, Serialize, Deserialize)]
+pub struct ServiceDescription {
+    pub name: String,
+    pub fingerprint: Fingerprint,
+    pub methods: Vec<MethodDescription>,
+    pub types: Vec<TypeDescription>,
+}
+
+#[derive(Debug, Clone, PartialEq, Schema, Serialize, Deserialize)]
+pub struct MethodDescription {
+    pub name: String,
+    pub kind: MethodKind,
+    pub params: Vec<FieldDescription>,
+    pub returns: TypeRef,
+}
+
+/// Use of a type.
+#[derive(Debug, Clone, PartialEq, Schema, Serialize, Deserialize)]
+pub enum TypeRef {
+    /// Named like in Rust, e.g. `u32` or `String`.
+    Primitive(String),
+    Sequence(Box<TypeRef>),
+    Tuple(Vec<TypeRef>),
+    /// A type described in [`ServiceDescription::types`], with the arguments for
+    /// its generic parameters.
+    Named {
+        name: String,
+        module_path: String,
+        params: Vec<TypeRef>,
+    },
+    /// A generic parameter of the type being described.
+    Parameter(String),
+}
+
+/// Definition of a struct or an enum.
+#[derive(Debug, Clone, PartialEq, Schema, Serialize, Deserialize)]
+pub struct TypeDescription {
+    pub name: String,
+    pub module_path: String,
+    /// Names of the generic parameters.
+    pub params: Vec<String>,
+    pub definition: Definition,
+}
+
+#[derive(Debug, Clone, PartialEq, Schema, Serialize, Deserialize)]
+pub enum Definition {
+    Struct(ShapeDescription),
+    Enum {
+        representation: Representation,
+        variants: Vec<VariantDescription>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Schema, Serialize, Deserialize)]
+pub enum ShapeDescription {
+    Unit,
+    Newtype(TypeRef),
+    Tuple(Vec<TypeRef>),
+    Map(Vec<FieldDescription>),
+}
+
+#[derive(Debug, Clone, PartialEq, Schema, Serialize, Deserialize)]
+pub struct FieldDescription {
+    pub name: String,
+    pub schema: TypeRef,
+}
+
+#[derive(Debug, Clone, PartialEq, Schema, Serialize, Deserialize)]
+pub struct VariantDescription {
+    pub name: String,
+    pub shape: ShapeDescription,
+}
+
+/// See [`EnumRepr`].
+#[derive(Debug, Clone, PartialEq, Schema, Serialize, Deserialize)]
+pub enum Representation {
+    External,
+    Adjacent { tag: String, content: String },
+}
+
+impl ServiceDescription {
+    pub fn of(service: &ServiceDef) -> Self {
+        let mut types = Types::default();
+        let methods = service
+            .methods
+            .iter()
+            .map(|method| MethodDescription {
+                name: method.name.to_string(),
+                kind: method.kind,
+                params: method
+                    .params
+                    .iter()
+                    .map(|param| types.field(param))
+                    .collect(),
+                returns: types.type_ref(method.returns.get()),
+            })
+            .collect();
+        ServiceDescription {
+            name: service.name.to_string(),
+            fingerprint: service.fingerprint,
+            methods,
+            types: types.described,
+        }
+    }
+
+    /// Definition of the type `type_ref` refers to, if it is a named one.
+    pub fn definition(&self, type_ref: &TypeRef) -> Option<&TypeDescription> {
+        let TypeRef::Named {
+            name, module_path, ..
+        } = type_ref
+        else {
+            return None;
+        };
+        self.types
+            .iter()
+            .find(|ty| ty.name == *name && ty.module_path == *module_path)
+    }
+
+    pub fn method(&self, name: &str) -> Option<&MethodDescription> {
+        self.methods.iter().find(|method| method.name == name)
+    }
+}
+
+impl TypeDescription {
+    /// Descriptions of every named type `schemas` use, each one once.
+    pub fn all_of(schemas: impl IntoIterator<Item = SchemaDef>) -> Vec<TypeDescription> {
+        let mut types = Types::default();
+        for schema in schemas {
+            types.type_ref(schema);
+        }
+        types.described
+    }
+}
+
+/// Named types described so far.
+#[derive(Default)]
+struct Types {
+    seen: BTreeSet<(&'static str, &'static str)>,
+    described: Vec<TypeDescription>,
+}
+
+impl Types {
+    fn type_ref(&mut self, schema: SchemaDef) -> TypeRef {
+        match schema {
+            SchemaDef::Primitive(_) => TypeRef::Primitive(schema.name().unwrap().to_string()),
+            SchemaDef::Sequence(item) => TypeRef::Sequence(Box::new(self.type_ref(item.get()))),
+            SchemaDef::Tuple(items) => TypeRef::Tuple(self.type_refs(items)),
+            SchemaDef::GenericParameter(name) => TypeRef::Parameter(name.to_string()),
+            SchemaDef::Struct(StructDef {
+                name,
+                module_path,
+                generic,
+                ..
+            })
+            | SchemaDef::Enum(EnumDef {
+                name,
+                module_path,
+                generic,
+                ..
+            }) => {
+                // Inserted before describing it, so that recursive types end.
+                if self.seen.insert((module_path, name)) {
+                    let description = self.describe(schema.generic_schema().unwrap_or(schema));
+                    self.described.push(description);
+                }
+                TypeRef::Named {
+                    name: name.to_string(),
+                    module_path: module_path.to_string(),
+                    params: self.type_refs(generic.map_or(&[], |generic| generic.params)),
+                }
+            }
+        }
+    }
+
+    fn type_refs(&mut self, schemas: &[SchemaPtr]) -> Vec<TypeRef> {
+        schemas
+            .iter()
+            .map(|schema| self.type_ref(schema.get()))
+            .collect()
+    }
+
+    fn field(&mut self, field: &FieldDef) -> FieldDescription {
+        FieldDescription {
+            name: field.name.to_string(),
+            schema: self.type_ref(field.schema.get()),
+        }
+    }
+
+    /// Describe the generic definition `schema` of a named type.
+    fn describe(&mut self, schema: SchemaDef) -> TypeDescription {
+        let (name, module_path, generic) = match schema {
+            SchemaDef::Struct(def) => (def.name, def.module_path, def.generic),
+            SchemaDef::Enum(def) => (def.name, def.module_path, def.generic),
+            _ => unreachable!("only named types are described"),
+        };
+        // The parameters of a generic definition are the parameters themselves.
+        let params = generic
+            .map_or(&[][..], |generic| generic.params)
+            .iter()
+            .filter_map(|param| match param.get() {
+                SchemaDef::GenericParameter(name) => Some(name.to_string()),
+                _ => None,
+            })
+            .collect();
+        let definition = match schema {
+            SchemaDef::Struct(def) => Definition::Struct(self.shape(def.shape)),
+            SchemaDef::Enum(def) => Definition::Enum {
+                representation: match def.representation {
+                    EnumRepr::External => Representation::External,
+                    EnumRepr::Adjacent { tag, content } => Representation::Adjacent {
+                        tag: tag.to_string(),
+                        content: content.to_string(),
+                    },
+                },
+                variants: def
+                    .variants
+                    .iter()
+                    .map(|variant| VariantDescription {
+                        name: variant.name.to_string(),
+                        shape: self.shape(variant.shape),
+                    })
+                    .collect(),
+            },
+            _ => unreachable!(),
+        };
+        TypeDescription {
+            name: name.to_string(),
+            module_path: module_path.to_string(),
+            params,
+            definition,
+        }
+    }
+
+    fn shape(&mut self, shape: Shape) -> ShapeDescription {
+        match shape {
+            Shape::Unit => ShapeDescription::Unit,
+            Shape::Newtype(schema) => ShapeDescription::Newtype(self.type_ref(schema.get())),
+            Shape::Tuple(fields) => ShapeDescription::Tuple(self.type_refs(fields)),
+            Shape::Map(fields) => {
+                ShapeDescription::Map(fields.iter().map(|field| self.field(field)).collect())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Schema)]
+    #[allow(dead_code)]
+    struct Tree {
+        value: Result<u32, String>,
+        children: Vec<Tree>,
+    }
+
+    const SERVICE: ServiceDef = ServiceDef {
+        name: "TreeService",
+        methods: &[MethodDef {
+            name: "grow",
+            kind: MethodKind::Request,
+            params: &[FieldDef {
+                name: "tree",
+                schema: SchemaPtr(<Tree as Schema>::schema),
+            }],
+            returns: SchemaPtr(<Vec<u32> as Schema>::schema),
+        }],
+        schema: SchemaPtr(<Tree as Schema>::schema),
+        fingerprint: Tree::FINGERPRINT,
+    };
+
+    #[test]
+    fn test_recursive_types_are_described_once() {
+        let description = ServiceDescription::of(&SERVICE);
+        let method = description.method("grow").unwrap();
+        let tree = description.definition(&method.params[0].schema).unwrap();
+        assert_eq!(tree.name, "Tree");
+
+        let names: Vec<_> = description.types.iter().map(|ty| &ty.name).collect();
+        assert_eq!(names, ["Result", "Tree"]);
+        let result = &description.types[0];
+        assert_eq!(result.params, ["T", "E"]);
+    }
+}
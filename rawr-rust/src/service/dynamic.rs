@@ -0,0 +1,451 @@
+use std::{collections::BTreeMap, pin::pin, sync::Arc};
+
+use futures::{
+    FutureExt, Sink, Stream,
+    future::{self, BoxFuture, Either},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    AbstractClient, DESCRIBE_METHOD, Definition, Envelope, MethodKind, Representation,
+    RequestError, Result, ServiceDescription, ShapeDescription, TypeRef,
+};
+
+/// Request to any method, encoded like the requests of generated clients:
+///
+/// ```json
+/// {"method": "complex", "payload": [{"name": "", "count": 0, ...}, 42]}
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DynamicRequest {
+    pub method: String,
+    pub payload: Value,
+}
+
+/// Response of any method, encoded like the responses of generated servers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DynamicResponse {
+    pub method: String,
+    pub payload: Value,
+}
+
+/// Client that calls methods by name, with arguments and return values as JSON,
+/// e.g. for scripts and debugging tools that aren't compiled against the
+/// generated client of a service.
+///
+/// Arguments are passed as an array, one element per parameter. With a
+/// [`ServiceDescription`], e.g. from [`describe`](DynamicClient::describe), they
+/// are checked before they are sent, so that a typo doesn't end up as a
+/// deserialization error on the server.
+///
+/// Only methods that respond once can be called. The wire format is JSON's, so
+/// the transport has to use the [`Json`](crate::codec::Json) codec.
+///
+/// A server can't decode a call to a method it doesn't have, so it drops it
+/// without responding. Give the client a [`timeout`](DynamicClient::timeout)
+/// to not wait for such calls forever.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let transport = rawr::transport::websocket::connect("ws://127.0.0.1:5555").await?;
+/// let (client, client_task) = DynamicClient::new(transport);
+/// let client = client.timeout(|| tokio::time::sleep(Duration::from_secs(5)));
+/// tokio::spawn(client_task);
+///
+/// let client = client.validate_with(client.describe().await?);
+/// let greeting = client.call("say_hello", json!(["World"])).await?;
+/// ```
+#[derive(Clone)]
+pub struct DynamicClient {
+    inner: AbstractClient<DynamicRequest, DynamicResponse>,
+    description: Option<Arc<ServiceDescription>>,
+    timeout: Option<Timeout>,
+}
+
+type Timeout = Arc<dyn Fn() -> BoxFuture<'static, ()> + Send + Sync>;
+
+impl DynamicClient {
+    pub fn new<Tx, Rx>(transport: (Tx, Rx)) -> (Self, impl Future<Output = ()>)
+    where
+        Tx: Sink<Envelope<DynamicRequest>>,
+        Rx: Stream<Item = Envelope<Result<DynamicResponse>>>,
+    {
+        let (inner, task) = AbstractClient::new(transport);
+        (Self::from(inner), task)
+    }
+
+    /// Check the arguments of every call against `description`, and respond
+    /// with [`RequestError::Invalid`] instead of sending calls that don't match.
+    pub fn validate_with(mut self, description: ServiceDescription) -> Self {
+        self.description = Some(Arc::new(description));
+        self
+    }
+
+    /// Fail calls with [`RequestError::TimedOut`] if the future returned by
+    /// `timeout` completes before they are responded to.
+    pub fn timeout<F>(mut self, timeout: impl Fn() -> F + Send + Sync + 'static) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.timeout = Some(Arc::new(move || timeout().boxed()));
+        self
+    }
+
+    /// Ask the server which service it offers, see [`DESCRIBE_METHOD`].
+    pub async fn describe(&self) -> Result<ServiceDescription> {
+        let payload = self.request(DESCRIBE_METHOD, Value::Null).await?;
+        serde_json::from_value(payload).map_err(|e| RequestError::Invalid(e.to_string()))
+    }
+
+    /// Call `method` with `args`, an array of one element per parameter.
+    pub async fn call(&self, method: &str, args: Value) -> Result<Value> {
+        let payload = self.payload(method, args, MethodKind::Request)?;
+        self.request(method, payload).await
+    }
+
+    /// Send a notification to `method`, without waiting for it to be handled.
+    pub fn notify(&self, method: &str, args: Value) -> Result<()> {
+        let payload = self.payload(method, args, MethodKind::Notification)?;
+        self.inner.notify(DynamicRequest {
+            method: method.to_string(),
+            payload,
+        })
+    }
+
+    async fn request(&self, method: &str, payload: Value) -> Result<Value> {
+        let req = DynamicRequest {
+            method: method.to_string(),
+            payload,
+        };
+        let response = self.inner.make_request(req);
+        let res = match &self.timeout {
+            Some(timeout) => match future::select(pin!(response), timeout()).await {
+                Either::Left((res, _)) => res,
+                Either::Right(_) => Err(RequestError::TimedOut),
+            },
+            None => response.await,
+        };
+        res.map(|res| res.payload)
+    }
+
+    /// Payload of a request to `method`: the arguments as a tuple, or `null` if
+    /// there are none, like the requests of generated clients.
+    fn payload(&self, method: &str, args: Value, kind: MethodKind) -> Result<Value> {
+        if let Some(description) = &self.description {
+            validate(description, method, &args, kind).map_err(RequestError::Invalid)?;
+        }
+        Ok(match args {
+            Value::Array(args) if args.is_empty() => Value::Null,
+            args => args,
+        })
+    }
+}
+
+impl From<AbstractClient<DynamicRequest, DynamicResponse>> for DynamicClient {
+    fn from(inner: AbstractClient<DynamicRequest, DynamicResponse>) -> Self {
+        DynamicClient {
+            inner,
+            description: None,
+            timeout: None,
+        }
+    }
+}
+
+/// Check that `args` are valid arguments of a call of `kind` to `method`.
+pub fn validate(
+    description: &ServiceDescription,
+    method: &str,
+    args: &Value,
+    kind: MethodKind,
+) -> std::result::Result<(), String> {
+    let Some(def) = description.method(method) else {
+        return Err(format!("{} has no method `{}`", description.name, method));
+    };
+    if def.kind != kind {
+        return Err(format!(
+            "`{}` is a {:?}, not a {:?}",
+            method, def.kind, kind
+        ));
+    }
+    let Value::Array(args) = args else {
+        return Err(format!("arguments of `{}` must be an array", method));
+    };
+    if args.len() != def.params.len() {
+        return Err(format!(
+            "`{}` takes {} arguments, got {}",
+            method,
+            def.params.len(),
+            args.len()
+        ));
+    }
+    let checker = Checker {
+        description,
+        params: BTreeMap::new(),
+    };
+    for (param, arg) in def.params.iter().zip(args) {
+        checker.check(arg, &param.schema, &param.name)?;
+    }
+    Ok(())
+}
+
+/// Checks JSON values against the types of a description.
+struct Checker<'a> {
+    description: &'a ServiceDescription,
+    /// Arguments of the generic parameters of the type being checked.
+    params: BTreeMap<&'a str, TypeRef>,
+}
+
+impl<'a> Checker<'a> {
+    fn check(&self, value: &Value, ty: &TypeRef, path: &str) -> std::result::Result<(), String> {
+        let mismatch = |expected: &str| Err(format!("{path}: expected {expected}, got {value}"));
+        match ty {
+            TypeRef::Primitive(name) => {
+                let valid = match name.as_str() {
+                    "()" => value.is_null(),
+                    "bool" => value.is_boolean(),
+                    "String" => value.is_string(),
+                    "char" => value.as_str().is_some_and(|s| s.chars().count() == 1),
+                    "f32" | "f64" => value.is_number(),
+                    "u8" => value.as_u64().is_some_and(|n| u8::try_from(n).is_ok()),
+                    "u16" => value.as_u64().is_some_and(|n| u16::try_from(n).is_ok()),
+                    "u32" => value.as_u64().is_some_and(|n| u32::try_from(n).is_ok()),
+                    "u64" => value.is_u64(),
+                    "i8" => value.as_i64().is_some_and(|n| i8::try_from(n).is_ok()),
+                    "i16" => value.as_i64().is_some_and(|n| i16::try_from(n).is_ok()),
+                    "i32" => value.as_i64().is_some_and(|n| i32::try_from(n).is_ok()),
+                    "i64" => value.is_i64(),
+                    // Unknown to this version, so anything goes.
+                    _ => true,
+                };
+                if !valid {
+                    return mismatch(name);
+                }
+            }
+            TypeRef::Sequence(item) => {
+                let Value::Array(items) = value else {
+                    return mismatch("an array");
+                };
+                for (i, value) in items.iter().enumerate() {
+                    self.check(value, item, &format!("{path}[{i}]"))?;
+                }
+            }
+            TypeRef::Tuple(items) => self.check_tuple(value, items, path)?,
+            TypeRef::Parameter(name) => {
+                if let Some(ty) = self.params.get(name.as_str()) {
+                    self.check(value, ty, path)?;
+                }
+            }
+            TypeRef::Named { name, params, .. } => {
+                let Some(def) = self.description.definition(ty) else {
+                    return Err(format!("{path}: `{name}` isn't described"));
+                };
+                // Resolve the arguments before they go out of scope.
+                let params = params.iter().map(|param| self.resolve(param));
+                let checker = Checker {
+                    description: self.description,
+                    params: def.params.iter().map(String::as_str).zip(params).collect(),
+                };
+                match &def.definition {
+                    Definition::Struct(shape) => checker.check_shape(value, shape, path)?,
+                    Definition::Enum {
+                        representation,
+                        variants,
+                    } => {
+                        let (variant, content) = match representation {
+                            Representation::External => match value {
+                                Value::String(variant) => (variant.as_str(), None),
+                                Value::Object(map) if map.len() == 1 => {
+                                    let (variant, content) = map.iter().next().unwrap();
+                                    (variant.as_str(), Some(content))
+                                }
+                                _ => return mismatch(&format!("a variant of {name}")),
+                            },
+                            Representation::Adjacent { tag, content } => {
+                                match value.get(tag).and_then(Value::as_str) {
+                                    Some(variant) => (variant, value.get(content)),
+                                    None => return mismatch(&format!("a variant of {name}")),
+                                }
+                            }
+                        };
+                        let Some(def) = variants.iter().find(|def| def.name == variant) else {
+                            return Err(format!("{path}: {name} has no variant `{variant}`"));
+                        };
+                        let path = format!("{path}.{variant}");
+                        match (&def.shape, content) {
+                            (ShapeDescription::Unit, None) => {}
+                            (shape, Some(content)) => checker.check_shape(content, shape, &path)?,
+                            (_, None) => return Err(format!("{path}: missing content")),
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_tuple(
+        &self,
+        value: &Value,
+        items: &[TypeRef],
+        path: &str,
+    ) -> std::result::Result<(), String> {
+        match value {
+            Value::Array(values) if values.len() == items.len() => {
+                for (i, (value, item)) in values.iter().zip(items).enumerate() {
+                    self.check(value, item, &format!("{path}.{i}"))?;
+                }
+                Ok(())
+            }
+            _ => Err(format!(
+                "{path}: expected an array of {} elements, got {value}",
+                items.len()
+            )),
+        }
+    }
+
+    fn check_shape(
+        &self,
+        value: &Value,
+        shape: &ShapeDescription,
+        path: &str,
+    ) -> std::result::Result<(), String> {
+        match shape {
+            ShapeDescription::Unit if value.is_null() => Ok(()),
+            ShapeDescription::Unit => Err(format!("{path}: expected null, got {value}")),
+            ShapeDescription::Newtype(ty) => self.check(value, ty, path),
+            ShapeDescription::Tuple(items) => self.check_tuple(value, items, path),
+            ShapeDescription::Map(fields) => {
+                let Value::Object(map) = value else {
+                    return Err(format!("{path}: expected an object, got {value}"));
+                };
+                for field in fields {
+                    let path = format!("{path}.{}", field.name);
+                    match map.get(&field.name) {
+                        Some(value) => self.check(value, &field.schema, &path)?,
+                        None => return Err(format!("{path}: missing")),
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// `ty` with the generic parameters in scope replaced by their arguments.
+    fn resolve(&self, ty: &TypeRef) -> TypeRef {
+        match ty {
+            TypeRef::Parameter(name) => match self.params.get(name.as_str()) {
+                Some(ty) => ty.clone(),
+                None => ty.clone(),
+            },
+            TypeRef::Sequence(item) => TypeRef::Sequence(Box::new(self.resolve(item))),
+            TypeRef::Tuple(items) => {
+                TypeRef::Tuple(items.iter().map(|ty| self.resolve(ty)).collect())
+            }
+            TypeRef::Named {
+                name,
+                module_path,
+                params,
+            } => TypeRef::Named {
+                name: name.clone(),
+                module_path: module_path.clone(),
+                params: params.iter().map(|ty| self.resolve(ty)).collect(),
+            },
+            TypeRef::Primitive(_) => ty.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        AbstractServer, FieldDef, MethodDef, Request, Responder, Schema, SchemaPtr, ServiceDef,
+        transport,
+    };
+
+    #[derive(Schema)]
+    #[allow(dead_code)]
+    struct Point {
+        x: i32,
+        y: std::result::Result<u8, String>,
+    }
+
+    const SERVICE: ServiceDef = ServiceDef {
+        name: "PointService",
+        methods: &[MethodDef {
+            name: "norm",
+            kind: MethodKind::Request,
+            params: &[FieldDef {
+                name: "point",
+                schema: SchemaPtr(<Point as Schema>::schema),
+            }],
+            returns: SchemaPtr(<u32 as Schema>::schema),
+        }],
+        schema: SchemaPtr(<Point as Schema>::schema),
+        fingerprint: Point::FINGERPRINT,
+    };
+
+    #[test]
+    fn test_call_by_name() {
+        let (client_transport, server_transport) = transport();
+        let (client, client_task) = DynamicClient::new(client_transport);
+        let client = client.validate_with(ServiceDescription::of(&SERVICE));
+
+        let handle_request =
+            async |req: Request<DynamicRequest>, responder: Responder<DynamicResponse>| {
+                let [point]: [Value; 1] = serde_json::from_value(req.data.payload).unwrap();
+                let x = point["x"].as_i64().unwrap();
+                let payload = json!(x.unsigned_abs());
+                let method = req.data.method;
+                responder
+                    .respond(Ok(DynamicResponse { method, payload }))
+                    .await;
+            };
+        let server_task = AbstractServer::new(server_transport, handle_request);
+
+        let test = async {
+            let point = json!({"x": -3, "y": {"Ok": 4}});
+            let res = client.call("norm", json!([point])).await;
+            assert_eq!(res.unwrap(), json!(3));
+
+            // Invalid calls aren't sent at all.
+            let point = json!({"x": -3, "y": {"Ok": 256}});
+            let res = client.call("norm", json!([point])).await;
+            let Err(RequestError::Invalid(reason)) = res else {
+                panic!("expected an invalid request");
+            };
+            assert_eq!(reason, "point.y.Ok: expected u8, got 256");
+            let res = client.call("norms", json!([])).await;
+            assert!(matches!(res, Err(RequestError::Invalid(_))));
+        };
+
+        let tasks = future::join(client_task, server_task);
+        block_on(future::select(pin!(test), pin!(tasks)));
+    }
+    #[test]
+    fn test_call_times_out() {
+        let (client_transport, server_transport) = transport();
+        let (client, client_task) = DynamicClient::new(client_transport);
+        let client = client.timeout(|| future::ready(()));
+
+        // Like a server that can't decode the request, never respond.
+        let handle_request =
+            async |_: Request<DynamicRequest>, _responder: Responder<DynamicResponse>| {
+                future::pending::<()>().await;
+            };
+        let server_task = AbstractServer::new(server_transport, handle_request);
+
+        let test = async {
+            let res = client.call("norm", json!([])).await;
+            assert!(matches!(res, Err(RequestError::TimedOut)));
+        };
+
+        let tasks = future::join(client_task, server_task);
+        block_on(future::select(pin!(test), pin!(tasks)));
+    }
+}
@@ -0,0 +1,321 @@
+use std::{fmt, marker::PhantomData};
+
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, IgnoredAny, MapAccess, Visitor},
+    ser::SerializeMap,
+};
+
+use super::{Hello, Meta, Packet};
+
+/// Everything a client and a server send to each other. Transports carry
+/// envelopes, not bare [`Packet`]s.
+///
+/// Envelopes are encoded as maps, and the keys present tell the kinds apart:
+///
+/// ```json
+/// {"id": 0, "data": ...}    // Packet
+/// {"id": 0, "chunk": ...}   // Chunk
+/// {"id": 0, "end": true}    // End
+/// {"id": 0, "credit": 16}   // Credit
+/// {"id": 0, "cancel": true} // Cancel
+/// {"notification": ...}     // Notification
+/// {"batch": [{"id": 0, "data": ...}, ...]} // Batch
+/// {"ping": true}            // Ping
+/// {"pong": true}            // Pong
+/// {"hello": {"version": 1, "schema": "8c0e7f2b1d3a5964"}} // Hello
+/// ```
+///
+/// Packets, chunks and notifications that carry [`Meta`]data have a `meta` entry
+/// as well:
+///
+/// ```json
+/// {"id": 0, "data": ..., "meta": {"trace": "4bf92f35"}}
+/// ```
+///
+/// A plain request or response is thus encoded exactly like the [`Packet`]
+/// inside it, so peers that only know about packets keep working.
+pub enum Envelope<P> {
+    /// Request, response, or one item of a response stream. All items of a
+    /// stream share the id of the request that opened it.
+    Packet(Packet<P>),
+    /// One item of the upload stream of the request with the same id. Sent by
+    /// the client, but only as many as the server has granted [`Credit`] for.
+    ///
+    /// [`Credit`]: Envelope::Credit
+    Chunk(Packet<P>),
+    /// Nothing more will be sent for this id. Sent by the server after the last
+    /// item of a response stream, and by the client after the last chunk of an
+    /// upload.
+    End(u32),
+    /// Sent by the server to allow the client to send this many more chunks of
+    /// the upload with this id.
+    Credit(u32, u32),
+    /// Sent by the client when it's no longer interested in the response with
+    /// this id, so the server can stop working on it.
+    Cancel(u32),
+    /// Request the client doesn't expect a response to, so it has no id. Carries
+    /// [`Meta`]data like a packet.
+    Notification(P, Meta),
+    /// Requests or responses that are sent together, e.g. to save round trips.
+    /// Each keeps its own id. The server responds to a batch of requests with a
+    /// batch of responses, except for streamed responses, which are sent as
+    /// packets of their own.
+    Batch(Vec<Packet<P>>),
+    /// Asks the other end to show that it's still there. Both clients and servers
+    /// answer with a [`Pong`](Envelope::Pong), see [`Heartbeat`](super::Heartbeat).
+    Ping,
+    /// Answer to a [`Ping`](Envelope::Ping).
+    Pong,
+    /// What the sender speaks, sent first thing after connecting. See
+    /// [`Handshake`](super::Handshake).
+    Hello(Hello),
+}
+
+impl<P> From<Packet<P>> for Envelope<P> {
+    fn from(packet: Packet<P>) -> Self {
+        Envelope::Packet(packet)
+    }
+}
+
+impl<P: Serialize> Serialize for Envelope<P> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let len = match self {
+            Envelope::Packet(packet) | Envelope::Chunk(packet) => packet_len(packet),
+            Envelope::Notification(_, meta) => 1 + meta_len(meta),
+            Envelope::Batch(_) | Envelope::Ping | Envelope::Pong | Envelope::Hello(_) => 1,
+            _ => 2,
+        };
+        let mut map = serializer.serialize_map(Some(len))?;
+        match self {
+            Envelope::Packet(packet) => serialize_packet(&mut map, "data", packet)?,
+            Envelope::Chunk(packet) => serialize_packet(&mut map, "chunk", packet)?,
+            Envelope::End(id) => {
+                map.serialize_entry("id", id)?;
+                map.serialize_entry("end", &true)?;
+            }
+            Envelope::Credit(id, credit) => {
+                map.serialize_entry("id", id)?;
+                map.serialize_entry("credit", credit)?;
+            }
+            Envelope::Cancel(id) => {
+                map.serialize_entry("id", id)?;
+                map.serialize_entry("cancel", &true)?;
+            }
+            Envelope::Notification(data, meta) => {
+                map.serialize_entry("notification", data)?;
+                serialize_meta(&mut map, meta)?;
+            }
+            Envelope::Batch(packets) => {
+                map.serialize_entry("batch", packets)?;
+            }
+            Envelope::Ping => map.serialize_entry("ping", &true)?,
+            Envelope::Pong => map.serialize_entry("pong", &true)?,
+            Envelope::Hello(hello) => map.serialize_entry("hello", hello)?,
+        }
+        map.end()
+    }
+}
+
+/// Number of map entries of `packet`.
+fn packet_len<P>(packet: &Packet<P>) -> usize {
+    2 + meta_len(&packet.meta)
+}
+
+/// Number of map entries of `meta`, which is left out when empty.
+fn meta_len(meta: &Meta) -> usize {
+    if meta.is_empty() { 0 } else { 1 }
+}
+
+fn serialize_packet<M: SerializeMap, P: Serialize>(
+    map: &mut M,
+    data_key: &'static str,
+    packet: &Packet<P>,
+) -> Result<(), M::Error> {
+    map.serialize_entry("id", &packet.id)?;
+    map.serialize_entry(data_key, &packet.data)?;
+    serialize_meta(map, &packet.meta)
+}
+
+fn serialize_meta<M: SerializeMap>(map: &mut M, meta: &Meta) -> Result<(), M::Error> {
+    // Left out when empty, so that peers that don't know about metadata keep
+    // working.
+    if !meta.is_empty() {
+        map.serialize_entry("meta", meta)?;
+    }
+    Ok(())
+}
+
+impl<P: Serialize> Serialize for Packet<P> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(packet_len(self)))?;
+        serialize_packet(&mut map, "data", self)?;
+        map.end()
+    }
+}
+
+impl<'de, P: Deserialize<'de>> Deserialize<'de> for Packet<P> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Envelope::deserialize(deserializer)? {
+            Envelope::Packet(packet) => Ok(packet),
+            _ => Err(de::Error::custom("expected a packet")),
+        }
+    }
+}
+
+impl<'de, P: Deserialize<'de>> Deserialize<'de> for Envelope<P> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(EnvelopeVisitor(PhantomData))
+    }
+}
+
+struct EnvelopeVisitor<P>(PhantomData<P>);
+
+/// Everything but the id of an envelope.
+enum Kind<P> {
+    Packet(P),
+    Chunk(P),
+    End,
+    Credit(u32),
+    Cancel,
+    Notification(P),
+    Batch(Vec<Packet<P>>),
+    Ping,
+    Pong,
+    Hello(Hello),
+}
+
+impl<'de, P: Deserialize<'de>> Visitor<'de> for EnvelopeVisitor<P> {
+    type Value = Envelope<P>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an envelope")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut id = None;
+        let mut meta = Meta::new();
+        let mut kind = None;
+        while let Some(key) = map.next_key::<Key>()? {
+            let next = match key {
+                Key::Id => {
+                    id = Some(map.next_value()?);
+                    continue;
+                }
+                Key::Meta => {
+                    meta = map.next_value()?;
+                    continue;
+                }
+                Key::Data => Kind::Packet(map.next_value()?),
+                Key::Chunk => Kind::Chunk(map.next_value()?),
+                Key::End => {
+                    map.next_value::<bool>()?;
+                    Kind::End
+                }
+                Key::Credit => Kind::Credit(map.next_value()?),
+                Key::Cancel => {
+                    map.next_value::<bool>()?;
+                    Kind::Cancel
+                }
+                Key::Notification => Kind::Notification(map.next_value()?),
+                Key::Batch => Kind::Batch(map.next_value()?),
+                Key::Ping => {
+                    map.next_value::<bool>()?;
+                    Kind::Ping
+                }
+                Key::Pong => {
+                    map.next_value::<bool>()?;
+                    Kind::Pong
+                }
+                Key::Hello => Kind::Hello(map.next_value()?),
+                // Leave room for extending the protocol. Only self-describing
+                // formats can skip values, others fail here.
+                Key::Unknown => {
+                    map.next_value::<IgnoredAny>()?;
+                    continue;
+                }
+            };
+            if kind.replace(next).is_some() {
+                return Err(de::Error::custom("envelope has more than one kind"));
+            }
+        }
+
+        // Every kind but notifications, batches, pings, pongs and hellos needs an
+        // id.
+        let id = || id.ok_or_else(|| de::Error::missing_field("id"));
+        Ok(
+            match kind.ok_or_else(|| de::Error::missing_field("data"))? {
+                Kind::Packet(data) => Envelope::Packet(Packet {
+                    id: id()?,
+                    data,
+                    meta,
+                }),
+                Kind::Chunk(data) => Envelope::Chunk(Packet {
+                    id: id()?,
+                    data,
+                    meta,
+                }),
+                Kind::End => Envelope::End(id()?),
+                Kind::Credit(credit) => Envelope::Credit(id()?, credit),
+                Kind::Cancel => Envelope::Cancel(id()?),
+                Kind::Notification(data) => Envelope::Notification(data, meta),
+                Kind::Batch(packets) => Envelope::Batch(packets),
+                Kind::Ping => Envelope::Ping,
+                Kind::Pong => Envelope::Pong,
+                Kind::Hello(hello) => Envelope::Hello(hello),
+            },
+        )
+    }
+}
+
+enum Key {
+    Id,
+    Meta,
+    Data,
+    Chunk,
+    End,
+    Credit,
+    Cancel,
+    Notification,
+    Batch,
+    Ping,
+    Pong,
+    Hello,
+    Unknown,
+}
+
+// NOTE: Keys are deserialized as strings rather than identifiers, because
+// non-self-describing formats (postcard) don't support identifiers.
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(KeyVisitor)
+    }
+}
+
+struct KeyVisitor;
+
+impl Visitor<'_> for KeyVisitor {
+    type Value = Key;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an envelope key")
+    }
+
+    fn visit_str<E: de::Error>(self, key: &str) -> Result<Key, E> {
+        Ok(match key {
+            "id" => Key::Id,
+            "meta" => Key::Meta,
+            "data" => Key::Data,
+            "chunk" => Key::Chunk,
+            "end" => Key::End,
+            "credit" => Key::Credit,
+            "cancel" => Key::Cancel,
+            "notification" => Key::Notification,
+            "batch" => Key::Batch,
+            "ping" => Key::Ping,
+            "pong" => Key::Pong,
+            "hello" => Key::Hello,
+            _ => Key::Unknown,
+        })
+    }
+}
@@ -0,0 +1,351 @@
+use std::{
+    pin::pin,
+    sync::{Arc, Mutex},
+};
+
+use futures::{
+    Sink, SinkExt, Stream, StreamExt,
+    channel::mpsc,
+    future,
+    stream::{self, PollNext},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{BoundedTx, Envelope, Packet, RequestError, Result, until_end};
+use crate::Fingerprint;
+
+/// Version of the protocol, i.e. of how [`Envelope`]s are encoded and what they
+/// mean. Bumped whenever an older peer can't understand a newer one.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// What a peer speaks: the version of the protocol, and the [`Fingerprint`] of
+/// its service. Sent as an [`Envelope::Hello`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hello {
+    pub version: u32,
+    pub schema: Fingerprint,
+}
+
+/// Makes sure both ends of a transport speak the same protocol and the same
+/// service, before a stale client runs into errors deep inside its codec.
+///
+/// Wraps a transport. The client sends an [`Envelope::Hello`] first thing, with
+/// [`PROTOCOL_VERSION`] and the fingerprint of its service, and the server
+/// answers with its own. An end that sees a different protocol version rejects
+/// the other end. A different service fingerprint is logged as a warning, or,
+/// with [`reject_mismatch`](Handshake::reject_mismatch), rejected as well.
+///
+/// Once rejected, requests aren't handled anymore, but responded to with
+/// [`RequestError::Incompatible`] explaining why. A server accepts clients that
+/// don't send a hello, so that clients without a handshake keep working.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let handshake = Handshake::new(TestServer::fingerprint()).reject_mismatch();
+/// let (transport, handshake_task) = handshake.server(transport);
+/// tokio::spawn(handshake_task);
+/// tokio::spawn(TestServer::new(transport, ServiceImpl));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Handshake {
+    schema: Fingerprint,
+    reject_mismatch: bool,
+}
+
+impl Handshake {
+    /// Speak the service with the fingerprint `schema`, and only warn about
+    /// peers that speak another one.
+    pub fn new(schema: Fingerprint) -> Self {
+        Handshake {
+            schema,
+            reject_mismatch: false,
+        }
+    }
+
+    /// Reject peers that speak another service, instead of warning about them.
+    pub fn reject_mismatch(mut self) -> Self {
+        self.reject_mismatch = true;
+        self
+    }
+
+    /// The hello this end sends.
+    pub fn hello(&self) -> Hello {
+        Hello {
+            version: PROTOCOL_VERSION,
+            schema: self.schema,
+        }
+    }
+
+    /// Wrap the transport of a client. Returns the wrapped transport, and a
+    /// future that must be spawned along with the client. It sends everything
+    /// the client sends, and completes once the client is dropped.
+    #[allow(clippy::type_complexity)]
+    pub fn client<Tx, Rx, Req, Res>(
+        self,
+        transport: (Tx, Rx),
+    ) -> (
+        (
+            BoundedTx<Envelope<Req>>,
+            impl Stream<Item = Envelope<Result<Res>>>,
+        ),
+        impl Future<Output = ()>,
+    )
+    where
+        Tx: Sink<Envelope<Req>>,
+        Rx: Stream<Item = Envelope<Result<Res>>>,
+    {
+        let (tx, rx) = transport;
+        let hello = self.hello();
+        let rejected = Rejected::default();
+        let (out_tx, mut out_rx) = mpsc::channel(0);
+        let (rejections_tx, rejections_rx) = mpsc::unbounded();
+
+        let rx = rx.filter_map({
+            let rejected = rejected.clone();
+            move |envelope| {
+                let envelope = match envelope {
+                    Envelope::Hello(hello) => {
+                        rejected.check(&self, hello, "server");
+                        None
+                    }
+                    envelope => Some(envelope),
+                };
+                future::ready(envelope)
+            }
+        });
+        let rx = until_end(rx, rejections_rx);
+
+        let task = async move {
+            let mut tx = pin!(tx);
+            if tx.send(Envelope::Hello(hello)).await.is_ok() {
+                while let Some(envelope) = out_rx.next().await {
+                    let envelope = match rejected.reason() {
+                        Some(reason) => match reject(envelope, &reason) {
+                            Rejection::Respond(response) => {
+                                rejections_tx.unbounded_send(response).ok();
+                                continue;
+                            }
+                            Rejection::Ignore => continue,
+                            Rejection::Pass(envelope) => envelope,
+                        },
+                        None => envelope,
+                    };
+                    if tx.send(envelope).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            tx.close().await.ok();
+        };
+
+        ((BoundedTx(out_tx), rx), task)
+    }
+
+    /// Wrap the transport of a server. Returns the wrapped transport, and a
+    /// future that must be spawned along with the server. It sends everything
+    /// the server sends, and completes once the connection is done.
+    #[allow(clippy::type_complexity)]
+    pub fn server<Rx, Tx, Req, Res>(
+        self,
+        transport: (Rx, Tx),
+    ) -> (
+        (
+            impl Stream<Item = Envelope<Req>>,
+            BoundedTx<Envelope<Result<Res>>>,
+        ),
+        impl Future<Output = ()>,
+    )
+    where
+        Rx: Stream<Item = Envelope<Req>>,
+        Tx: Sink<Envelope<Result<Res>>>,
+    {
+        let (rx, tx) = transport;
+        let rejected = Rejected::default();
+        let (out_tx, out_rx) = mpsc::channel(0);
+        // Hellos and rejections, which go before any response.
+        let (replies_tx, replies_rx) = mpsc::unbounded();
+
+        let rx = rx.filter_map(move |envelope| {
+            let envelope = match envelope {
+                Envelope::Hello(hello) => {
+                    replies_tx
+                        .unbounded_send(Envelope::Hello(self.hello()))
+                        .ok();
+                    rejected.check(&self, hello, "client");
+                    None
+                }
+                envelope => match rejected.reason() {
+                    Some(reason) => match reject(envelope, &reason) {
+                        Rejection::Respond(response) => {
+                            replies_tx.unbounded_send(response).ok();
+                            None
+                        }
+                        Rejection::Ignore => None,
+                        Rejection::Pass(envelope) => Some(envelope),
+                    },
+                    None => Some(envelope),
+                },
+            };
+            future::ready(envelope)
+        });
+
+        let task = async move {
+            let mut tx = pin!(tx);
+            let prefer_replies = |_: &mut ()| PollNext::Left;
+            let mut outgoing = stream::select_with_strategy(replies_rx, out_rx, prefer_replies);
+            while let Some(envelope) = outgoing.next().await {
+                if tx.send(envelope).await.is_err() {
+                    break;
+                }
+            }
+            tx.close().await.ok();
+        };
+
+        ((rx, BoundedTx(out_tx)), task)
+    }
+}
+
+/// Why the other end was rejected, if it was. Shared by both halves of a
+/// wrapped transport.
+#[derive(Clone, Default)]
+struct Rejected(Arc<Mutex<Option<String>>>);
+
+impl Rejected {
+    /// Compare the `hello` of the `peer` with the one of this end.
+    fn check(&self, handshake: &Handshake, hello: Hello, peer: &str) {
+        let ours = handshake.hello();
+        let reason = if hello.version != ours.version {
+            format!(
+                "{peer} speaks protocol version {}, expected {}",
+                hello.version, ours.version
+            )
+        } else if hello.schema != ours.schema {
+            let reason = format!(
+                "{peer} speaks service {}, expected {}",
+                hello.schema, ours.schema
+            );
+            if !handshake.reject_mismatch {
+                log::warn!("{}", reason);
+                return;
+            }
+            reason
+        } else {
+            return;
+        };
+        log::error!("Rejecting {}: {}", peer, reason);
+        *self.0.lock().unwrap() = Some(reason);
+    }
+
+    fn reason(&self) -> Option<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+enum Rejection<Req, Res> {
+    /// Respond to the request(s) on behalf of the other end.
+    Respond(Envelope<Result<Res>>),
+    /// Drop it, there is nothing to respond to.
+    Ignore,
+    /// Send it as usual, it isn't part of a request.
+    Pass(Envelope<Req>),
+}
+
+/// What to do with a request `envelope` after the other end has been rejected
+/// for `reason`.
+fn reject<Req, Res>(envelope: Envelope<Req>, reason: &str) -> Rejection<Req, Res> {
+    let error = |id| Packet::new(id, Err(RequestError::Incompatible(reason.to_string())));
+    match envelope {
+        Envelope::Packet(packet) => Rejection::Respond(Envelope::Packet(error(packet.id))),
+        Envelope::Batch(packets) => Rejection::Respond(Envelope::Batch(
+            packets.iter().map(|p| error(p.id)).collect(),
+        )),
+        Envelope::Chunk(_) | Envelope::Notification(..) => Rejection::Ignore,
+        envelope => Rejection::Pass(envelope),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::{AbstractClient, AbstractServer, Request, Responder, transport};
+
+    fn serve<Tx, Rx>(transport: (Rx, Tx)) -> impl Future<Output = ()>
+    where
+        Rx: Stream<Item = Envelope<u32>>,
+        Tx: Sink<Envelope<Result<u32>>>,
+    {
+        let handle_request = async |req: Request<u32>, responder: Responder<u32>| {
+            responder.respond(Ok(req.data)).await;
+        };
+        AbstractServer::new(transport, handle_request)
+    }
+
+    #[test]
+    fn test_matching_peers_are_served() {
+        let (client_transport, server_transport) = transport();
+        let handshake = Handshake::new(Fingerprint::of::<u32>()).reject_mismatch();
+        let (client_transport, client_handshake) = handshake.clone().client(client_transport);
+        let (server_transport, server_handshake) = handshake.server(server_transport);
+        let (client, client_task) = AbstractClient::<u32, u32>::new(client_transport);
+
+        let test = async {
+            assert_eq!(client.make_request(1).await.unwrap(), 1);
+        };
+
+        let tasks = future::join4(
+            client_task,
+            client_handshake,
+            serve(server_transport),
+            server_handshake,
+        );
+        block_on(future::select(pin!(test), pin!(tasks)));
+    }
+
+    #[test]
+    fn test_mismatching_client_is_rejected() {
+        let (client_transport, server_transport) = transport();
+        let client_handshake = Handshake::new(Fingerprint::of::<String>());
+        let server_handshake = Handshake::new(Fingerprint::of::<u32>()).reject_mismatch();
+        let (client_transport, client_handshake) = client_handshake.client(client_transport);
+        let (server_transport, server_handshake) = server_handshake.server(server_transport);
+        let (client, client_task) = AbstractClient::<u32, u32>::new(client_transport);
+
+        let test = async {
+            let res = client.make_request(1).await;
+            assert!(matches!(res, Err(RequestError::Incompatible(_))));
+        };
+
+        let tasks = future::join4(
+            client_task,
+            client_handshake,
+            serve(server_transport),
+            server_handshake,
+        );
+        block_on(future::select(pin!(test), pin!(tasks)));
+    }
+
+    #[derive(crate::Schema)]
+    #[allow(dead_code)]
+    struct Tree {
+        children: Vec<Tree>,
+    }
+
+    #[test]
+    fn test_fingerprint_is_stable() {
+        // Computed at compile time, even for types that contain themselves.
+        const TREE: Fingerprint = Fingerprint::of::<Tree>();
+        assert_ne!(TREE, Fingerprint::of::<Vec<Tree>>());
+
+        assert_eq!(Fingerprint::of::<u32>(), Fingerprint::of::<u32>());
+        assert_ne!(Fingerprint::of::<u32>(), Fingerprint::of::<u64>());
+        assert_ne!(
+            Fingerprint::of::<std::result::Result<u32, String>>(),
+            Fingerprint::of::<std::result::Result<String, u32>>()
+        );
+        let fingerprint = Fingerprint::of::<Vec<(u8, bool)>>();
+        assert_eq!(fingerprint.to_string().parse(), Ok(fingerprint));
+    }
+}
@@ -0,0 +1,258 @@
+use std::{
+    pin::pin,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use futures::{
+    Sink, SinkExt, Stream, StreamExt,
+    channel::{mpsc, oneshot},
+    future::{self, Either},
+};
+
+use super::{BoundedTx, Envelope};
+
+/// Detects that the other end of a transport is gone, even if the transport
+/// doesn't notice, like a half-open TCP connection.
+///
+/// Wraps a transport, and sends an [`Envelope::Ping`] whenever the future
+/// returned by `interval` completes. Clients and servers answer pings, so a
+/// peer that sends nothing at all for [`max_missed`](Heartbeat::max_missed)
+/// intervals in a row is considered gone, even while the transport doesn't take
+/// anything anymore. The wrapped transport is then closed:
+/// a client fails its pending requests with
+/// [`RequestError::TransportClosed`](super::RequestError::TransportClosed), and
+/// a server stops serving the connection.
+///
+/// Either end, or both, may send heartbeats. Since pings are part of the
+/// protocol, this works over any transport.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let interval = || tokio::time::sleep(Duration::from_secs(10));
+/// let (transport, heartbeat_task) = Heartbeat::new(interval).client(transport);
+/// let (client, client_task) = TestClient::new(transport);
+/// tokio::spawn(heartbeat_task);
+/// tokio::spawn(client_task);
+/// ```
+pub struct Heartbeat<I> {
+    interval: I,
+    max_missed: u32,
+}
+
+impl<I, F> Heartbeat<I>
+where
+    I: Fn() -> F,
+    F: Future<Output = ()>,
+{
+    /// Ping every `interval`, and give up after 3 silent intervals.
+    pub fn new(interval: I) -> Self {
+        Heartbeat {
+            interval,
+            max_missed: 3,
+        }
+    }
+
+    /// Give up once the other end has sent nothing for `intervals` intervals in a
+    /// row. `0` is treated as `1`, as the other end needs at least one interval to
+    /// answer.
+    pub fn max_missed(mut self, intervals: u32) -> Self {
+        self.max_missed = intervals.max(1);
+        self
+    }
+
+    /// Wrap the transport of a client. Returns the wrapped transport, and a
+    /// future that must be spawned along with the client. It sends everything
+    /// the client sends, and completes once the transport is closed.
+    pub fn client<Tx, Rx, Out, In>(
+        self,
+        transport: (Tx, Rx),
+    ) -> (
+        (BoundedTx<Envelope<Out>>, impl Stream<Item = Envelope<In>>),
+        impl Future<Output = ()>,
+    )
+    where
+        Tx: Sink<Envelope<Out>>,
+        Rx: Stream<Item = Envelope<In>>,
+    {
+        let (tx, rx) = transport;
+        let (tx, rx, task) = self.wrap(tx, rx);
+        ((tx, rx), task)
+    }
+
+    /// Like [`client`](Heartbeat::client), for the transport of a server.
+    pub fn server<Rx, Tx, In, Out>(
+        self,
+        transport: (Rx, Tx),
+    ) -> (
+        (impl Stream<Item = Envelope<In>>, BoundedTx<Envelope<Out>>),
+        impl Future<Output = ()>,
+    )
+    where
+        Rx: Stream<Item = Envelope<In>>,
+        Tx: Sink<Envelope<Out>>,
+    {
+        let (rx, tx) = transport;
+        let (tx, rx, task) = self.wrap(tx, rx);
+        ((rx, tx), task)
+    }
+
+    fn wrap<Tx, Rx, Out, In>(
+        self,
+        tx: Tx,
+        rx: Rx,
+    ) -> (
+        BoundedTx<Envelope<Out>>,
+        impl Stream<Item = Envelope<In>>,
+        impl Future<Output = ()>,
+    )
+    where
+        Tx: Sink<Envelope<Out>>,
+        Rx: Stream<Item = Envelope<In>>,
+    {
+        // Set whenever anything arrives, and reset on every interval.
+        let received = Arc::new(AtomicBool::new(false));
+        // Dropped once the transport is closed, which ends the wrapped stream.
+        let (closed_tx, closed_rx) = oneshot::channel::<()>();
+        let (out_tx, mut out_rx) = mpsc::channel(0);
+
+        let rx = rx
+            .inspect({
+                let received = received.clone();
+                move |_| received.store(true, Ordering::SeqCst)
+            })
+            .take_until(closed_rx);
+
+        let task = async move {
+            let mut tx = pin!(tx);
+            let mut tick = pin!((self.interval)());
+            let mut missed = 0;
+            // Called on every interval. Returns whether the other end is gone.
+            let mut gone = || {
+                if received.swap(false, Ordering::SeqCst) {
+                    missed = 0;
+                } else {
+                    missed += 1;
+                }
+                if missed >= self.max_missed {
+                    log::warn!("No heartbeat for {} intervals, closing", missed);
+                }
+                missed >= self.max_missed
+            };
+            loop {
+                let envelope = match future::select(out_rx.next(), tick.as_mut()).await {
+                    Either::Left((Some(envelope), _)) => envelope,
+                    // Nothing is going to be sent anymore.
+                    Either::Left((None, _)) => {
+                        tx.close().await.ok();
+                        break;
+                    }
+                    Either::Right(((), _)) => {
+                        tick.set((self.interval)());
+                        if gone() {
+                            break;
+                        }
+                        Envelope::Ping
+                    }
+                };
+
+                // Keep counting intervals while the transport doesn't take the
+                // envelope, e.g. because the other end stopped reading.
+                let mut send = pin!(tx.send(envelope));
+                let sent = loop {
+                    match future::select(send.as_mut(), tick.as_mut()).await {
+                        Either::Left((res, _)) => break res.is_ok(),
+                        Either::Right(((), _)) => {
+                            tick.set((self.interval)());
+                            if gone() {
+                                break false;
+                            }
+                        }
+                    }
+                };
+                if !sent {
+                    break;
+                }
+            }
+            drop(closed_tx);
+        };
+
+        (BoundedTx(out_tx), rx, task)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::{
+        AbstractClient, AbstractServer, Packet, Request, RequestError, Responder, transport,
+        transport_bounded,
+    };
+
+    #[test]
+    fn test_silent_server_closes_client() {
+        let (client_transport, _server_transport) = transport();
+
+        // The server never reads, let alone answers.
+        let heartbeat = Heartbeat::new(|| future::ready(()));
+        let (client_transport, heartbeat_task) = heartbeat.client(client_transport);
+        let (client, client_task) = AbstractClient::<u32, u32>::new(client_transport);
+
+        let test = async {
+            let res = client.make_request(1).await;
+            assert!(matches!(res, Err(RequestError::TransportClosed)));
+        };
+
+        block_on(future::join3(test, client_task, heartbeat_task));
+    }
+
+    #[test]
+    fn test_full_transport_closes_client() {
+        let (client_transport, _server_transport) = transport_bounded(0);
+
+        // The server never reads, so sending blocks once the channel is full.
+        let heartbeat = Heartbeat::new(|| future::ready(()));
+        let (client_transport, heartbeat_task) = heartbeat.client(client_transport);
+        let (client, client_task) = AbstractClient::<u32, u32>::new(client_transport);
+
+        let test = async {
+            let (first, second) =
+                future::join(client.make_request(1), client.make_request(2)).await;
+            assert!(matches!(first, Err(RequestError::TransportClosed)));
+            assert!(matches!(second, Err(RequestError::TransportClosed)));
+        };
+
+        block_on(future::join3(test, client_task, heartbeat_task));
+    }
+
+    #[test]
+    fn test_max_missed_is_at_least_one() {
+        let heartbeat = Heartbeat::new(|| future::ready(())).max_missed(0);
+        assert_eq!(heartbeat.max_missed, 1);
+    }
+
+    #[test]
+    fn test_server_answers_pings() {
+        let (client_transport, server_transport) = transport();
+        let (tx, mut rx) = client_transport;
+        let handle_request = async |req: Request<u32>, responder: Responder<u32>| {
+            responder.respond(Ok(req.data)).await;
+        };
+        let server_task = AbstractServer::new(server_transport, handle_request);
+
+        let test = async {
+            tx.send(Envelope::Ping);
+            assert!(matches!(rx.next().await, Some(Envelope::Pong)));
+            // Requests are still served as usual.
+            tx.send(Envelope::Packet(Packet::new(0, 1)));
+            assert!(matches!(rx.next().await, Some(Envelope::Packet(_))));
+        };
+
+        block_on(future::select(pin!(test), pin!(server_task)));
+    }
+}
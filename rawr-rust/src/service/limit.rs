@@ -0,0 +1,298 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use futures::channel::oneshot;
+
+use super::{Handler, Method, Middleware, Request, RequestError, Responder};
+
+/// Limits how many requests are handled at once, in total and per method, so
+/// that a burst of requests can't start thousands of expensive handlers.
+///
+/// Requests over the limit wait in a queue until a running request finishes,
+/// or, with [`reject_excess`](ConcurrencyLimit::reject_excess), are responded to
+/// with [`RequestError::Overloaded`] right away.
+///
+/// Limits are shared by all clones, so a limit added to a [`Server`](super::Server)
+/// applies to all of its connections together.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let limit = ConcurrencyLimit::new(64).method("render", 4).reject_excess();
+/// let server = Server::new(handle_request).layer(limit.clone());
+/// tokio::spawn(server.serve(transports));
+///
+/// println!("{} requests waiting", limit.queued());
+/// ```
+#[derive(Clone)]
+pub struct ConcurrencyLimit {
+    total: Option<Semaphore>,
+    methods: HashMap<&'static str, Semaphore>,
+    reject_excess: bool,
+    queued: Arc<AtomicUsize>,
+}
+
+impl ConcurrencyLimit {
+    /// At most `max` requests at once, whatever their method.
+    pub fn new(max: usize) -> Self {
+        ConcurrencyLimit {
+            total: Some(Semaphore::new(max)),
+            ..Self::per_method()
+        }
+    }
+
+    /// No limit in total, only the ones added with
+    /// [`method`](ConcurrencyLimit::method).
+    pub fn per_method() -> Self {
+        ConcurrencyLimit {
+            total: None,
+            methods: HashMap::new(),
+            reject_excess: false,
+            queued: Arc::default(),
+        }
+    }
+
+    /// At most `max` requests to `method` at once. They count towards the total
+    /// limit as well.
+    pub fn method(mut self, method: &'static str, max: usize) -> Self {
+        self.methods.insert(method, Semaphore::new(max));
+        self
+    }
+
+    /// Respond to requests over the limit with [`RequestError::Overloaded`],
+    /// instead of queueing them.
+    pub fn reject_excess(mut self) -> Self {
+        self.reject_excess = true;
+        self
+    }
+
+    /// Number of requests waiting for a running request to finish.
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    async fn acquire(&self, semaphore: &Semaphore) -> Option<Permit> {
+        if let Some(permit) = semaphore.try_acquire() {
+            return Some(permit);
+        }
+        if self.reject_excess {
+            return None;
+        }
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        let _queued = Decrement(&self.queued);
+        Some(semaphore.acquire().await)
+    }
+}
+
+impl<Req: Method, Res> Middleware<Req, Res> for ConcurrencyLimit {
+    async fn call(
+        &self,
+        req: Request<Req>,
+        responder: Responder<Res>,
+        next: &impl Handler<Req, Res>,
+    ) {
+        // Always the method first, so that requests holding the total permit
+        // never wait for a method permit.
+        let method = self.methods.get(req.data.method());
+        let semaphores = method.into_iter().chain(&self.total);
+        let mut permits = Vec::new();
+        for semaphore in semaphores {
+            match self.acquire(semaphore).await {
+                Some(permit) => permits.push(permit),
+                None => return responder.respond(Err(RequestError::Overloaded)).await,
+            }
+        }
+        next.handle(req, responder).await;
+    }
+}
+
+struct Decrement<'a>(&'a AtomicUsize);
+
+impl Drop for Decrement<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Hands out a limited number of permits, in the order they were asked for.
+#[derive(Clone)]
+struct Semaphore(Arc<Mutex<SemaphoreState>>);
+
+struct SemaphoreState {
+    available: usize,
+    waiting: VecDeque<oneshot::Sender<Permit>>,
+}
+
+impl Semaphore {
+    fn new(permits: usize) -> Self {
+        Semaphore(Arc::new(Mutex::new(SemaphoreState {
+            available: permits,
+            waiting: VecDeque::new(),
+        })))
+    }
+
+    fn try_acquire(&self) -> Option<Permit> {
+        let mut state = self.0.lock().unwrap();
+        // Don't jump the queue.
+        if state.available == 0 || !state.waiting.is_empty() {
+            return None;
+        }
+        state.available -= 1;
+        Some(Permit(Some(self.clone())))
+    }
+
+    async fn acquire(&self) -> Permit {
+        let rx = {
+            let mut state = self.0.lock().unwrap();
+            if state.available > 0 && state.waiting.is_empty() {
+                state.available -= 1;
+                return Permit(Some(self.clone()));
+            }
+            let (tx, rx) = oneshot::channel();
+            state.waiting.push_back(tx);
+            rx
+        };
+        // Senders are only dropped after sending.
+        rx.await.unwrap()
+    }
+
+    fn release(&self) {
+        let mut state = self.0.lock().unwrap();
+        // Hand the permit over to the next waiter that is still there.
+        while let Some(tx) = state.waiting.pop_front() {
+            match tx.send(Permit(Some(self.clone()))) {
+                Ok(()) => return,
+                // Defuse it, it would release again.
+                Err(mut permit) => _ = permit.0.take(),
+            }
+        }
+        state.available += 1;
+    }
+}
+
+/// Released when dropped.
+struct Permit(Option<Semaphore>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(semaphore) = self.0.take() {
+            semaphore.release();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{pin::pin, task::Poll};
+
+    use futures::{
+        FutureExt, StreamExt,
+        channel::mpsc,
+        executor::block_on,
+        future::{self, Shared},
+        stream,
+    };
+
+    use super::*;
+    use crate::{AbstractClient, Server, transport};
+
+    /// Every request is to the same method.
+    struct Render;
+
+    impl Method for Render {
+        fn method(&self) -> &'static str {
+            "render"
+        }
+    }
+
+    /// Lets the other futures of the task run before continuing.
+    async fn yield_now() {
+        let mut yielded = false;
+        future::poll_fn(|cx| {
+            if yielded {
+                return Poll::Ready(());
+            }
+            yielded = true;
+            // Wake right away, so that the task is polled again.
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Responds once released.
+    fn handler(
+        started_tx: mpsc::UnboundedSender<()>,
+        release_rx: Shared<oneshot::Receiver<()>>,
+    ) -> impl AsyncFn(Request<Render>, Responder<()>) + Clone {
+        async move |_, responder| {
+            started_tx.unbounded_send(()).unwrap();
+            release_rx.clone().await.unwrap();
+            responder.respond(Ok(())).await;
+        }
+    }
+
+    #[test]
+    fn test_excess_requests_are_rejected() {
+        let (client_transport, server_transport) = transport();
+        let (client, client_task) = AbstractClient::new(client_transport);
+
+        let (started_tx, mut started_rx) = mpsc::unbounded();
+        let (release_tx, release_rx) = oneshot::channel();
+        let limit = ConcurrencyLimit::per_method()
+            .method("render", 1)
+            .reject_excess();
+        let server = Server::new(handler(started_tx, release_rx.shared())).layer(limit);
+        let server_task = server.serve(stream::iter([server_transport]));
+
+        let test = async {
+            let first = client.make_request(Render);
+            let second = async {
+                started_rx.next().await;
+                let res = client.make_request(Render).await;
+                release_tx.send(()).unwrap();
+                res
+            };
+            let (first, second) = future::join(first, second).await;
+            assert!(first.is_ok());
+            assert!(matches!(second, Err(RequestError::Overloaded)));
+        };
+
+        let tasks = future::join(client_task, server_task);
+        block_on(future::select(pin!(test), pin!(tasks)));
+    }
+
+    #[test]
+    fn test_excess_requests_are_queued() {
+        let (client_transport, server_transport) = transport();
+        let (client, client_task) = AbstractClient::new(client_transport);
+
+        let (started_tx, mut started_rx) = mpsc::unbounded();
+        let (release_tx, release_rx) = oneshot::channel();
+        let limit = ConcurrencyLimit::new(1);
+        let server = Server::new(handler(started_tx, release_rx.shared())).layer(limit.clone());
+        let server_task = server.serve(stream::iter([server_transport]));
+
+        let test = async {
+            let requests = future::join(client.make_request(Render), client.make_request(Render));
+            let release = async {
+                started_rx.next().await;
+                while limit.queued() == 0 {
+                    yield_now().await;
+                }
+                release_tx.send(()).unwrap();
+            };
+            let ((first, second), ()) = future::join(requests, release).await;
+            assert!(first.is_ok() && second.is_ok());
+            assert_eq!(limit.queued(), 0);
+        };
+
+        let tasks = future::join(client_task, server_task);
+        block_on(future::select(pin!(test), pin!(tasks)));
+    }
+}
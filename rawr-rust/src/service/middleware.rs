@@ -0,0 +1,222 @@
+use std::{mem, sync::Arc};
+
+use super::{Envelope, Request, Responder, Result};
+
+/// Name of the method a request calls. Implemented by generated request enums,
+/// so that middleware and clients can tell requests apart without knowing their
+/// type.
+pub trait Method {
+    fn method(&self) -> &'static str;
+
+    /// Whether handling the request twice does no more harm than handling it
+    /// once, e.g. because it only reads. A [reconnecting](super::Reconnect)
+    /// client sends such requests again when the connection is lost before their
+    /// response arrives, and fails all others.
+    fn is_idempotent(&self) -> bool {
+        false
+    }
+}
+
+//// Server
+
+/// Handles requests. Implemented by every `AsyncFn(Request, Responder)`, and by
+/// handlers wrapped in [`Middleware`].
+#[allow(async_fn_in_trait)]
+pub trait Handler<Req, Res> {
+    async fn handle(&self, req: Request<Req>, responder: Responder<Res>);
+}
+
+impl<Req, Res, F> Handler<Req, Res> for F
+where
+    F: AsyncFn(Request<Req>, Responder<Res>),
+{
+    async fn handle(&self, req: Request<Req>, responder: Responder<Res>) {
+        self(req, responder).await
+    }
+}
+
+/// Wraps the handling of every request of a [`Server`](super::Server), e.g. for
+/// logging, authentication or metrics.
+///
+/// A middleware gets the request and its responder, and usually passes both on
+/// to `next`. It may also respond on its own instead, or drop the responder to
+/// reject the request with [`RequestError::Cancelled`](super::RequestError).
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// #[derive(Clone)]
+/// struct Logging;
+///
+/// impl<Req: Method, Res> Middleware<Req, Res> for Logging {
+///     async fn call(
+///         &self,
+///         req: Request<Req>,
+///         responder: Responder<Res>,
+///         next: &impl Handler<Req, Res>,
+///     ) {
+///         let (id, method) = (req.id, req.data.method());
+///         log::info!("{:?} {} started", id, method);
+///         next.handle(req, responder).await;
+///         log::info!("{:?} {} done", id, method);
+///     }
+/// }
+///
+/// Server::new(handle_request).layer(Logging).serve(transports).await;
+/// ```
+#[allow(async_fn_in_trait)]
+pub trait Middleware<Req, Res> {
+    async fn call(
+        &self,
+        req: Request<Req>,
+        responder: Responder<Res>,
+        next: &impl Handler<Req, Res>,
+    );
+}
+
+/// A handler wrapped in a middleware. Built by
+/// [`Server::layer`](super::Server::layer).
+#[derive(Clone)]
+pub struct Layered<M, H> {
+    middleware: M,
+    inner: H,
+}
+
+impl<M, H> Layered<M, H> {
+    pub fn new(middleware: M, inner: H) -> Self {
+        Layered { middleware, inner }
+    }
+}
+
+impl<Req, Res, M, H> Handler<Req, Res> for Layered<M, H>
+where
+    M: Middleware<Req, Res>,
+    H: Handler<Req, Res>,
+{
+    async fn handle(&self, req: Request<Req>, responder: Responder<Res>) {
+        self.middleware.call(req, responder, &self.inner).await
+    }
+}
+
+//// Client
+
+/// Sees every envelope an [`AbstractClient`](super::AbstractClient) sends or
+/// receives, e.g. for logging or metrics. Added with
+/// [`AbstractClient::intercept`](super::AbstractClient::intercept).
+///
+/// The packets of an [`Envelope::Batch`] are passed one at a time, each as an
+/// [`Envelope::Packet`], so an interceptor that handles packets sees every
+/// request and response. Turning one of them into another kind of envelope
+/// drops it from the batch.
+pub trait Interceptor<Req, Res>: Send + Sync {
+    /// Called before an envelope is sent to the server.
+    fn request(&self, envelope: &mut Envelope<Req>) {
+        let _ = envelope;
+    }
+
+    /// Called when an envelope arrives from the server, before it is dispatched
+    /// to the caller.
+    fn response(&self, envelope: &mut Envelope<Result<Res>>) {
+        let _ = envelope;
+    }
+}
+
+/// Interceptors of a client, in the order they were added.
+pub(super) type Interceptors<Req, Res> =
+    Arc<std::sync::RwLock<Vec<Box<dyn Interceptor<Req, Res>>>>>;
+
+/// Pass a request through every interceptor.
+pub(super) fn intercept_request<Req, Res>(
+    interceptors: &Interceptors<Req, Res>,
+    envelope: &mut Envelope<Req>,
+) {
+    let interceptors = interceptors.read().unwrap();
+    for_each_packet(envelope, |envelope| {
+        for interceptor in interceptors.iter() {
+            interceptor.request(envelope);
+        }
+    });
+}
+
+/// Pass a response through every interceptor.
+pub(super) fn intercept_response<Req, Res>(
+    interceptors: &Interceptors<Req, Res>,
+    envelope: &mut Envelope<Result<Res>>,
+) {
+    let interceptors = interceptors.read().unwrap();
+    for_each_packet(envelope, |envelope| {
+        for interceptor in interceptors.iter() {
+            interceptor.response(envelope);
+        }
+    });
+}
+
+/// Call `f` with `envelope`, or with each packet of it if it is a batch.
+fn for_each_packet<P>(envelope: &mut Envelope<P>, f: impl Fn(&mut Envelope<P>)) {
+    let Envelope::Batch(packets) = envelope else {
+        return f(envelope);
+    };
+    *packets = mem::take(packets)
+        .into_iter()
+        .filter_map(|packet| {
+            let mut envelope = Envelope::Packet(packet);
+            f(&mut envelope);
+            match envelope {
+                Envelope::Packet(packet) => Some(packet),
+                _ => None,
+            }
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, pin::pin};
+
+    use futures::{executor::block_on, future};
+
+    use super::*;
+    use crate::{AbstractClient, AbstractServer, transport};
+
+    /// Records the order in which requests pass through it.
+    struct Record<'a>(&'static str, &'a RefCell<Vec<&'static str>>);
+
+    impl Middleware<u32, u32> for Record<'_> {
+        async fn call(
+            &self,
+            req: Request<u32>,
+            responder: Responder<u32>,
+            next: &impl Handler<u32, u32>,
+        ) {
+            self.1.borrow_mut().push(self.0);
+            next.handle(req, responder).await;
+        }
+    }
+
+    #[test]
+    fn test_last_layer_is_outermost() {
+        let (client_transport, server_transport) = transport();
+        let (client, client_task) = AbstractClient::new(client_transport);
+
+        let calls = RefCell::new(Vec::new());
+        let handle_request = async |req: Request<u32>, responder: Responder<u32>| {
+            calls.borrow_mut().push("handler");
+            responder.respond(Ok(req.data)).await;
+        };
+        let handler = Layered::new(
+            Record("outer", &calls),
+            Layered::new(Record("inner", &calls), handle_request),
+        );
+        let server_task = AbstractServer::new(server_transport, async |req, responder| {
+            handler.handle(req, responder).await
+        });
+
+        let test = async {
+            assert_eq!(client.make_request(1).await.unwrap(), 1);
+            assert_eq!(*calls.borrow(), ["outer", "inner", "handler"]);
+        };
+
+        let tasks = future::join(client_task, server_task);
+        block_on(future::select(pin!(test), pin!(tasks)));
+    }
+}
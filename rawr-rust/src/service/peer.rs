@@ -0,0 +1,133 @@
+use std::pin::pin;
+
+use futures::{Sink, SinkExt, Stream, StreamExt, channel::mpsc, future, stream};
+use serde::{Deserialize, Serialize};
+
+use super::{AbstractClient, AbstractServer, Envelope, Request, Responder, Result};
+
+/// Everything two peers send to each other. Every peer is both a client and a
+/// server, and the two roles are kept apart on the wire:
+///
+/// ```json
+/// {"request": {"id": 0, "data": ...}}          // to the service of the other peer
+/// {"response": {"id": 0, "data": {"Ok": ...}}} // from the service of this peer
+/// ```
+///
+/// Each peer numbers the requests it makes on its own, so request ids only have
+/// to be unique per direction.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerMessage<Req, Res> {
+    /// Sent by the client half of a peer.
+    Request(Envelope<Req>),
+    /// Sent by the server half of a peer.
+    Response(Envelope<Result<Res>>),
+}
+
+pub struct AbstractPeer;
+
+impl AbstractPeer {
+    /// Serve requests of the other end of `transport` with `handle_request`, and
+    /// return a client for calling the service of the other end over the same
+    /// transport.
+    ///
+    /// Like [`AbstractClient::new`], this returns a future that must be spawned on
+    /// a runtime. It completes once the other end has closed the connection.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,ignore
+    /// let transport = rawr::transport::websocket::accept_peer(stream, Json).await?;
+    /// let handle_request = async |req, responder| {
+    ///     TestServer::handle_request(&ServiceImpl, req, responder).await
+    /// };
+    /// let (ui, task) = AbstractPeer::serve(transport, handle_request);
+    /// tokio::spawn(task);
+    ///
+    /// // Call the service exported by the other end.
+    /// let ui = UiClient::from(ui);
+    /// let confirmed = ui.confirm("Delete everything?".to_string()).await?;
+    /// ```
+    pub fn serve<Req, Res, PeerReq, PeerRes, Tx, Rx>(
+        transport: (Tx, Rx),
+        handle_request: impl AsyncFn(Request<Req>, Responder<Res>),
+    ) -> (AbstractClient<PeerReq, PeerRes>, impl Future<Output = ()>)
+    where
+        Tx: Sink<PeerMessage<PeerReq, Res>>,
+        Rx: Stream<Item = PeerMessage<Req, PeerRes>>,
+    {
+        let (tx, rx) = transport;
+        let (client_tx, client_rx) = mpsc::channel(0);
+        let (mut responses_tx, responses_rx) = mpsc::channel(0);
+        let (server_tx, server_rx) = mpsc::channel(0);
+        let (mut requests_tx, requests_rx) = mpsc::channel(0);
+
+        let (client, client_task) = AbstractClient::new((client_tx, responses_rx));
+        let server_task = AbstractServer::new((requests_rx, server_tx), handle_request);
+
+        let task = async move {
+            // Once the other end is gone, dropping the senders stops both halves.
+            let dispatch = async move {
+                let mut rx = pin!(rx);
+                while let Some(message) = rx.next().await {
+                    match message {
+                        PeerMessage::Request(envelope) => requests_tx.send(envelope).await.ok(),
+                        PeerMessage::Response(envelope) => responses_tx.send(envelope).await.ok(),
+                    };
+                }
+            };
+            let forward = async {
+                let messages = stream::select(
+                    client_rx.map(PeerMessage::Request),
+                    server_rx.map(PeerMessage::Response),
+                );
+                // If the other end is gone, there is no one left to send to.
+                messages.map(Ok).forward(tx).await.ok();
+            };
+            future::join4(dispatch, client_task, server_task, forward).await;
+        };
+
+        (client, task)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::peer_transport;
+
+    #[test]
+    fn test_peers_call_each_other() {
+        let (a_transport, b_transport) = peer_transport();
+
+        // `a` doubles even numbers, but only after asking `b` whether they are even.
+        let a_client = OnceCell::new();
+        let handle_a = async |req: Request<u32>, responder: Responder<u32>| {
+            let b: &AbstractClient<u32, bool> = a_client.get().unwrap();
+            let res = match b.make_request(req.data).await {
+                Ok(true) => Ok(req.data * 2),
+                Ok(false) => Ok(req.data),
+                Err(e) => Err(e),
+            };
+            responder.respond(res).await;
+        };
+        let handle_b = async |req: Request<u32>, responder: Responder<bool>| {
+            responder.respond(Ok(req.data.is_multiple_of(2))).await;
+        };
+        let (client, a_task) = AbstractPeer::serve(a_transport, handle_a);
+        a_client.set(client).ok();
+        let (b_client, b_task) = AbstractPeer::serve(b_transport, handle_b);
+
+        let test = async {
+            assert_eq!(b_client.make_request(4).await.unwrap(), 8);
+            assert_eq!(b_client.make_request(3).await.unwrap(), 3);
+        };
+
+        let tasks = future::join(a_task, b_task);
+        block_on(future::select(pin!(test), pin!(tasks)));
+    }
+}
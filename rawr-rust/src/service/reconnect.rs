@@ -0,0 +1,351 @@
+use std::{collections::BTreeMap, fmt::Display, pin::pin, slice, sync::Mutex, time::Duration};
+
+use futures::{
+    Sink, SinkExt, Stream, StreamExt,
+    channel::mpsc,
+    future::{self, Either},
+};
+
+use super::{
+    AbstractClient, Envelope, Method, Packet, Pending, RequestError, Result,
+    dispatch_server_responses, fail_pending, fail_pending_unless, intercept_request,
+};
+use crate::dashmap::DashMap;
+
+/// State of the connection of a [reconnecting](AbstractClient::reconnecting)
+/// client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Dialing for the first time.
+    Connecting,
+    Connected,
+    /// The connection was lost, and is being dialed again.
+    Reconnecting,
+    /// The client gave up dialing, or was dropped.
+    Closed,
+}
+
+/// How a [reconnecting](AbstractClient::reconnecting) client dials its
+/// transport: with `dial`, and after a failed attempt, again after a delay that
+/// doubles with every attempt that fails in a row.
+///
+/// The delay is waited for with `sleep`, which keeps this independent of any
+/// runtime.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let dial = || rawr::transport::websocket::connect("ws://127.0.0.1:5555");
+/// let reconnect = Reconnect::new(dial, tokio::time::sleep)
+///     .backoff(Duration::from_millis(100), Duration::from_secs(10));
+///
+/// let mut states = reconnect.states();
+/// let (client, client_task) = AbstractClient::reconnecting(reconnect);
+/// tokio::spawn(client_task);
+///
+/// while let Some(state) = states.next().await {
+///     show_connection_state(state);
+/// }
+/// ```
+pub struct Reconnect<D, S> {
+    dial: D,
+    sleep: S,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    max_attempts: Option<u32>,
+    states: States,
+}
+
+impl<D, S> Reconnect<D, S> {
+    /// Dial with `dial`, retrying forever after 100ms, 200ms, 400ms... up to 30s.
+    pub fn new(dial: D, sleep: S) -> Self {
+        Reconnect {
+            dial,
+            sleep,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+            max_attempts: None,
+            states: States::default(),
+        }
+    }
+
+    /// Wait `initial` after the first failed attempt, and twice as long after
+    /// every further one, but never longer than `max`.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Give up after `attempts` failed attempts in a row, and close the client.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = Some(attempts);
+        self
+    }
+
+    /// Stream of the state of the connection, starting with the current one. It
+    /// ends after [`ConnectionState::Closed`].
+    pub fn states(&self) -> mpsc::UnboundedReceiver<ConnectionState> {
+        self.states.subscribe()
+    }
+
+    /// Delay after `failures` failed attempts in a row.
+    fn delay(&self, failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+impl<Req: Method + Clone, Res> AbstractClient<Req, Res> {
+    /// Create a client that dials its transport as configured by `reconnect`,
+    /// and dials again whenever the connection is lost. The client, and all of
+    /// its clones, keep working across connections.
+    ///
+    /// Requests made while disconnected wait until the client is connected again.
+    /// When a connection is lost, requests that are waiting for a response are
+    /// sent again if they are [idempotent](Method::is_idempotent), and fail with
+    /// [`RequestError::TransportClosed`] otherwise, as the server may have
+    /// handled them already. Stream and upload requests always fail.
+    ///
+    /// The returned future completes once the client gives up dialing, or the
+    /// client and all of its clones are dropped.
+    pub fn reconnecting<D, T, E, S, F, Tx, Rx>(
+        reconnect: Reconnect<D, S>,
+    ) -> (Self, impl Future<Output = ()>)
+    where
+        D: Fn() -> T,
+        T: Future<Output = std::result::Result<(Tx, Rx), E>>,
+        E: Display,
+        S: Fn(Duration) -> F,
+        F: Future<Output = ()>,
+        Tx: Sink<Envelope<Req>>,
+        Rx: Stream<Item = Envelope<Result<Res>>>,
+    {
+        let (client, outgoing) = Self::unconnected();
+        let requests = client.requests.clone();
+        let interceptors = client.interceptors.clone();
+
+        let task = async move {
+            let pong_tx = outgoing.pong_tx.clone();
+            let mut outgoing = pin!(outgoing.stream(|requests| requests));
+            // Idempotent requests that were sent, in case the connection is lost
+            // before their response arrives.
+            let mut sent = BTreeMap::new();
+            let mut state = ConnectionState::Connecting;
+            let mut failures = 0;
+            loop {
+                reconnect.states.set(state);
+                let (server_tx, server_rx) = match (reconnect.dial)().await {
+                    Ok(transport) => transport,
+                    Err(e) => {
+                        log::warn!("Failed to connect: {}", e);
+                        failures += 1;
+                        if reconnect.max_attempts.is_some_and(|max| failures >= max) {
+                            break;
+                        }
+                        (reconnect.sleep)(reconnect.delay(failures)).await;
+                        continue;
+                    }
+                };
+                failures = 0;
+                reconnect.states.set(ConnectionState::Connected);
+
+                // Completes with `true` once the client is dropped, and with
+                // `false` if the transport fails.
+                let forward_requests = async {
+                    let mut server_tx = pin!(server_tx);
+                    // Requests lost with the previous connection go first.
+                    for packet in sent.values() {
+                        let envelope = Envelope::Packet(Packet::clone(packet));
+                        if server_tx.send(envelope).await.is_err() {
+                            return false;
+                        }
+                    }
+                    while let Some(mut envelope) = outgoing.next().await {
+                        intercept_request(&interceptors, &mut envelope);
+                        remember_idempotent(&mut sent, &requests, &envelope);
+                        if server_tx.send(envelope).await.is_err() {
+                            return false;
+                        }
+                    }
+                    server_tx.close().await.ok();
+                    true
+                };
+                let dispatch_responses =
+                    dispatch_server_responses(server_rx, &requests, &interceptors, &pong_tx);
+                let dropped =
+                    match future::select(pin!(forward_requests), pin!(dispatch_responses)).await {
+                        Either::Left((dropped, _)) => dropped,
+                        Either::Right(_) => false,
+                    };
+                if dropped {
+                    break;
+                }
+
+                log::info!("Connection lost, reconnecting");
+                sent.retain(|id, _| requests.contains_key(id));
+                let error = RequestError::TransportClosed;
+                fail_pending_unless(&requests, error, |id, _| sent.contains_key(&id));
+                state = ConnectionState::Reconnecting;
+            }
+
+            reconnect.states.set(ConnectionState::Closed);
+            fail_pending(&requests, RequestError::TransportClosed);
+        };
+
+        (client, task)
+    }
+}
+
+/// Keep a copy of the idempotent requests in `envelope`, and forget the ones
+/// that have been responded to since.
+fn remember_idempotent<Req: Method + Clone, Res>(
+    sent: &mut BTreeMap<u32, Packet<Req>>,
+    requests: &DashMap<u32, Pending<Res>>,
+    envelope: &Envelope<Req>,
+) {
+    sent.retain(|id, _| requests.contains_key(id));
+    let packets = match envelope {
+        Envelope::Packet(packet) => slice::from_ref(packet),
+        Envelope::Batch(packets) => packets,
+        _ => &[],
+    };
+    for packet in packets {
+        let pending = requests.get(&packet.id);
+        let single_response = matches!(pending.as_deref(), Some(Pending::Response(_)));
+        if single_response && packet.data.is_idempotent() {
+            sent.insert(packet.id, packet.clone());
+        }
+    }
+}
+
+/// Current connection state, and everyone who wants to know when it changes.
+#[derive(Default)]
+struct States(Mutex<StatesInner>);
+
+struct StatesInner {
+    current: ConnectionState,
+    subscribers: Vec<mpsc::UnboundedSender<ConnectionState>>,
+}
+
+impl Default for StatesInner {
+    fn default() -> Self {
+        StatesInner {
+            current: ConnectionState::Connecting,
+            subscribers: Vec::new(),
+        }
+    }
+}
+
+impl States {
+    fn subscribe(&self) -> mpsc::UnboundedReceiver<ConnectionState> {
+        let mut inner = self.0.lock().unwrap();
+        let (tx, rx) = mpsc::unbounded();
+        tx.unbounded_send(inner.current).ok();
+        inner.subscribers.push(tx);
+        rx
+    }
+
+    fn set(&self, state: ConnectionState) {
+        let mut inner = self.0.lock().unwrap();
+        if inner.current == state {
+            return;
+        }
+        inner.current = state;
+        inner
+            .subscribers
+            .retain(|tx| tx.unbounded_send(state).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::{AbstractServer, Request, Responder, transport};
+
+    #[derive(Clone)]
+    enum Op {
+        Read(u32),
+        Write(u32),
+    }
+
+    impl Method for Op {
+        fn method(&self) -> &'static str {
+            match self {
+                Op::Read(_) => "read",
+                Op::Write(_) => "write",
+            }
+        }
+
+        fn is_idempotent(&self) -> bool {
+            matches!(self, Op::Read(_))
+        }
+    }
+
+    #[test]
+    fn test_idempotent_requests_are_sent_again() {
+        let (first_transport, (mut first_rx, first_tx)) = transport();
+        let (second_transport, second_server_transport) = transport();
+        let transports = Mutex::new(vec![first_transport, second_transport].into_iter());
+        let dial = move || {
+            let transport = transports.lock().unwrap().next();
+            async move { transport.ok_or("no server left") }
+        };
+        let reconnect = Reconnect::new(dial, |_| future::ready(()));
+        let states = reconnect.states();
+        let (client, client_task) = AbstractClient::reconnecting(reconnect);
+
+        // The first server loses the connection once both requests have arrived.
+        let first_server = async move {
+            first_rx.next().await;
+            first_rx.next().await;
+            drop((first_rx, first_tx));
+        };
+        let handle_request = async |req: Request<Op>, responder: Responder<u32>| {
+            let (Op::Read(n) | Op::Write(n)) = req.data;
+            responder.respond(Ok(n)).await;
+        };
+        let second_server = AbstractServer::new(second_server_transport, handle_request);
+
+        let test = async {
+            let read = client.make_request(Op::Read(1));
+            let write = client.make_request(Op::Write(2));
+            let (read, write) = future::join(read, write).await;
+            assert_eq!(read.unwrap(), 1);
+            assert!(matches!(write, Err(RequestError::TransportClosed)));
+
+            let states: Vec<_> = states.take(4).collect().await;
+            use ConnectionState::*;
+            assert_eq!(states, [Connecting, Connected, Reconnecting, Connected]);
+        };
+
+        let tasks = future::join3(client_task, first_server, second_server);
+        block_on(future::select(pin!(test), pin!(tasks)));
+    }
+
+    #[test]
+    fn test_task_completes_once_client_is_dropped() {
+        let (client_transport, server_transport) = transport();
+        let transport = Mutex::new(Some(client_transport));
+        let dial = move || {
+            let transport = transport.lock().unwrap().take();
+            async move { transport.ok_or("no server left") }
+        };
+        let reconnect = Reconnect::new(dial, |_| future::ready(()));
+        let states = reconnect.states();
+        let (client, client_task) = AbstractClient::<Op, u32>::reconnecting(reconnect);
+
+        // The server is still there, but no one is left to make requests.
+        drop(client);
+        block_on(client_task);
+        drop(server_transport);
+
+        let states: Vec<_> = block_on(states.collect());
+        use ConnectionState::*;
+        assert_eq!(states, [Connecting, Connected, Closed]);
+    }
+}
@@ -0,0 +1,357 @@
+use std::{
+    any::Any,
+    fmt::{self, Display, Formatter},
+    net::SocketAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
+
+use futures::{FutureExt, Sink, Stream, StreamExt};
+
+use super::{
+    AbstractServer, Connection, Envelope, Handler, Layered, Request, Responder, Result,
+    ShutdownHandle, Spawn, Spawned, State,
+};
+
+/// Serves one service to many clients at once.
+///
+/// Every connection gets its own dispatcher, so packet ids of different clients
+/// never get mixed up. Every dispatcher gets a clone of the request handler, which
+/// may be wrapped in [`Middleware`](super::Middleware) with [`layer`](Server::layer).
+///
+/// By default, the dispatchers run within the future of the server, so that
+/// neither they nor the handler have to be `Send`. With
+/// [`spawn_with`](Server::spawn_with), every connection runs as a task of its own
+/// instead.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let transports = accept_connections(listener);
+///
+/// let server = Server::new(handle_request).layer(Logging);
+/// let connections = server.connections();
+/// tokio::spawn(server.serve(transports));
+///
+/// println!("{} clients connected", connections.active());
+/// ```
+pub struct Server<H, S = ()> {
+    handle_request: H,
+    /// Spawns the connections, if they don't run within the server's future.
+    spawner: S,
+    connections: Connections,
+    shutdown: ShutdownHandle,
+}
+
+impl<H> Server<H> {
+    pub fn new(handle_request: H) -> Self {
+        Server {
+            handle_request,
+            spawner: (),
+            connections: Connections::default(),
+            shutdown: ShutdownHandle::new(),
+        }
+    }
+
+    /// Wrap the request handler in `middleware`. Middleware added last is
+    /// outermost, so it sees requests first and finishes last.
+    pub fn layer<M>(self, middleware: M) -> Server<Layered<M, H>> {
+        Server {
+            handle_request: Layered::new(middleware, self.handle_request),
+            spawner: (),
+            connections: self.connections,
+            shutdown: self.shutdown,
+        }
+    }
+
+    /// Run every connection, and every request, as a task of its own on
+    /// `spawner`, see [`Spawned`]. A connection that keeps its thread busy then
+    /// doesn't hold up the others.
+    ///
+    /// Spawned tasks have to be `Send`, which middleware can't promise, so the
+    /// handler has to be a plain function.
+    pub fn spawn_with<S: Spawn + Clone>(self, spawner: S) -> Server<Spawned<S, H>, S> {
+        Server {
+            handle_request: Spawned::new(spawner.clone(), self.handle_request),
+            spawner,
+            connections: self.connections,
+            shutdown: self.shutdown,
+        }
+    }
+
+    /// Serve every transport yielded by `transports` concurrently. The future
+    /// completes once `transports` ends and all connections have been closed.
+    pub async fn serve<Req, Res, Rx, Tx>(self, transports: impl Stream<Item = (Rx, Tx)>)
+    where
+        H: Handler<Req, Res> + Clone,
+        Rx: Stream<Item = Envelope<Req>>,
+        Tx: Sink<Envelope<Result<Res>>>,
+    {
+        self.serve_accepted(transports.map(Accepted::new)).await
+    }
+
+    /// Like [`serve`](Server::serve), for transports that come with what is known
+    /// about the other end, which handlers then find in their [`Context`].
+    ///
+    /// [`Context`]: super::Context
+    pub async fn serve_accepted<Req, Res, Rx, Tx>(
+        self,
+        accepted: impl Stream<Item = Accepted<Rx, Tx>>,
+    ) where
+        H: Handler<Req, Res> + Clone,
+        Rx: Stream<Item = Envelope<Req>>,
+        Tx: Sink<Envelope<Result<Res>>>,
+    {
+        let serve_connection = |accepted| {
+            let handler = self.handle_request.clone();
+            let handle_request =
+                async move |req: Request<Req>, responder| handler.handle(req, responder).await;
+            self.serve_connection(accepted, handle_request)
+        };
+        let accepted = accepted.take_until(self.shutdown.stopped());
+        accepted.for_each_concurrent(None, serve_connection).await;
+    }
+}
+
+impl<S, H> Server<Spawned<S, H>, S>
+where
+    S: Spawn + Clone + Send + Sync + 'static,
+{
+    /// Like [`Server::serve`], with every connection spawned on the spawner.
+    pub async fn serve<Req, Res, Rx, Tx, F>(self, transports: impl Stream<Item = (Rx, Tx)>)
+    where
+        H: Fn(Request<Req>, Responder<Res>) -> F + Clone + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+        Req: Send + 'static,
+        Res: Send + 'static,
+        Rx: Stream<Item = Envelope<Req>> + Send + 'static,
+        Tx: Sink<Envelope<Result<Res>>> + Send + 'static,
+    {
+        self.serve_accepted(transports.map(Accepted::new)).await
+    }
+
+    /// Like [`Server::serve_accepted`], with every connection spawned on the
+    /// spawner.
+    pub async fn serve_accepted<Req, Res, Rx, Tx, F>(
+        self,
+        accepted: impl Stream<Item = Accepted<Rx, Tx>>,
+    ) where
+        H: Fn(Request<Req>, Responder<Res>) -> F + Clone + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+        Req: Send + 'static,
+        Res: Send + 'static,
+        Rx: Stream<Item = Envelope<Req>> + Send + 'static,
+        Tx: Sink<Envelope<Result<Res>>> + Send + 'static,
+    {
+        let serve_connection = |accepted| {
+            let handler = self.handle_request.clone();
+            let handle_request =
+                async move |req: Request<Req>, responder| handler.spawn(req, responder).await;
+            // Dropping the handle, e.g. along with the server, drops the task too.
+            let (task, handle) = self
+                .serve_connection(accepted, handle_request)
+                .remote_handle();
+            self.spawner.spawn(task);
+            handle
+        };
+        let accepted = accepted.take_until(self.shutdown.stopped());
+        accepted.for_each_concurrent(None, serve_connection).await;
+    }
+}
+
+impl<H, S> Server<H, S> {
+    /// Returns a handle for observing the connections of this server.
+    pub fn connections(&self) -> Connections {
+        self.connections.clone()
+    }
+
+    /// Returns a handle for shutting this server down gracefully. On shutdown,
+    /// the server stops accepting connections, and every connection stops
+    /// accepting requests.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Dispatcher of one connection, handing its requests to `handle_request`.
+    fn serve_connection<Req, Res, Rx, Tx, C>(
+        &self,
+        accepted: Accepted<Rx, Tx>,
+        handle_request: C,
+    ) -> impl Future<Output = ()> + use<H, S, Req, Res, Rx, Tx, C>
+    where
+        C: AsyncFn(Request<Req>, Responder<Res>),
+        Rx: Stream<Item = Envelope<Req>>,
+        Tx: Sink<Envelope<Result<Res>>>,
+    {
+        let guard = self.connections.open();
+        let shutdown = self.shutdown.clone();
+        async move {
+            log::debug!("{} opened", guard.id);
+            let connection = Connection {
+                id: Some(guard.id),
+                peer_addr: accepted.peer_addr,
+                state: accepted.state,
+            };
+            let (transport, connection) = (accepted.transport, Arc::new(connection));
+            AbstractServer::serve_connection(transport, connection, shutdown, handle_request).await;
+            log::debug!("{} closed", guard.id);
+        }
+    }
+}
+
+/// A transport accepted by a [`Server`], along with what is known about the
+/// other end.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let (stream, addr) = listener.accept().await?;
+/// let user = authenticate(&stream).await?;
+/// let transport = rawr::transport::stream::server(stream, Json);
+/// Accepted::new(transport).peer_addr(addr).with(user)
+/// ```
+pub struct Accepted<Rx, Tx> {
+    transport: (Rx, Tx),
+    peer_addr: Option<SocketAddr>,
+    state: State,
+}
+
+impl<Rx, Tx> Accepted<Rx, Tx> {
+    pub fn new(transport: (Rx, Tx)) -> Self {
+        Accepted {
+            transport,
+            peer_addr: None,
+            state: State::default(),
+        }
+    }
+
+    /// Address of the client, see [`Context::peer_addr`](super::Context::peer_addr).
+    pub fn peer_addr(mut self, addr: SocketAddr) -> Self {
+        self.peer_addr = Some(addr);
+        self
+    }
+
+    /// Attach `value` to the connection, see [`Context::get`](super::Context::get).
+    pub fn with<T: Any + Send + Sync>(mut self, value: T) -> Self {
+        self.state.insert(value);
+        self
+    }
+}
+
+/// Unique identifier of a connection within a [`Server`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ConnectionId(pub u64);
+
+impl Display for ConnectionId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Connection #{}", self.0)
+    }
+}
+
+/// Shared view of the connections of a [`Server`].
+#[derive(Clone, Default)]
+pub struct Connections {
+    active: Arc<AtomicUsize>,
+    total: Arc<AtomicU64>,
+}
+
+impl Connections {
+    /// Number of connections that are currently being served.
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// Number of connections that have been accepted since the server started.
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::SeqCst)
+    }
+
+    fn open(&self) -> ConnectionGuard {
+        let id = ConnectionId(self.total.fetch_add(1, Ordering::SeqCst));
+        self.active.fetch_add(1, Ordering::SeqCst);
+        ConnectionGuard {
+            id,
+            active: self.active.clone(),
+        }
+    }
+}
+
+/// Keeps the connection counted as active until dropped, even if the server
+/// future is dropped mid-connection.
+struct ConnectionGuard {
+    id: ConnectionId,
+    active: Arc<AtomicUsize>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::pin,
+        sync::{Arc, Barrier},
+        thread,
+    };
+
+    use futures::{executor::block_on, future, stream};
+
+    use super::*;
+    use crate::{AbstractClient, Context, transport};
+
+    /// Runs every future on a thread of its own.
+    #[derive(Clone)]
+    struct Threads;
+
+    impl Spawn for Threads {
+        fn spawn(&self, future: impl Future<Output = ()> + Send + 'static) {
+            thread::spawn(move || block_on(future));
+        }
+    }
+
+    struct User(&'static str);
+
+    #[test]
+    fn test_spawned_connections_keep_their_responses() {
+        let (ada_transport, ada_server_transport) = transport();
+        let (bob_transport, bob_server_transport) = transport();
+        let (ada, ada_task) = AbstractClient::new(ada_transport);
+        let (bob, bob_task) = AbstractClient::new(bob_transport);
+
+        // Blocks until both requests are being handled, so that both clients
+        // are waiting for a response with the same id at the same time.
+        let barrier = Arc::new(Barrier::new(2));
+        let handle_request = move |req: Request<u32>, responder: Responder<String>| {
+            let barrier = barrier.clone();
+            async move {
+                barrier.wait();
+                let user = Context::current().unwrap().get::<User>().unwrap().0;
+                responder
+                    .respond(Ok(format!("{} for {}", req.data, user)))
+                    .await;
+            }
+        };
+        let accepted = stream::iter([
+            Accepted::new(ada_server_transport).with(User("ada")),
+            Accepted::new(bob_server_transport).with(User("bob")),
+        ]);
+        let server = Server::new(handle_request).spawn_with(Threads);
+        let connections = server.connections();
+        let server_task = server.serve_accepted(accepted);
+
+        let test = async {
+            let (ada_res, bob_res) = future::join(ada.make_request(1), bob.make_request(2)).await;
+            assert_eq!(ada_res.unwrap(), "1 for ada");
+            assert_eq!(bob_res.unwrap(), "2 for bob");
+            assert_eq!(connections.total(), 2);
+        };
+
+        let tasks = future::join3(ada_task, bob_task, server_task);
+        block_on(future::select(pin!(test), pin!(tasks)));
+    }
+}
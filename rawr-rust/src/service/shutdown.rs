@@ -0,0 +1,205 @@
+use std::{
+    pin::pin,
+    sync::{Arc, Mutex},
+};
+
+use futures::{
+    FutureExt, StreamExt,
+    channel::{mpsc, oneshot},
+    future::{self, Either, Shared},
+    lock,
+};
+
+/// Shuts servers down gracefully: they stop accepting requests, finish the ones
+/// they are handling and send the last responses.
+///
+/// Get one from [`Server::shutdown_handle`](super::Server::shutdown_handle), or
+/// create one and pass it to [`AbstractServer::with_shutdown`](super::AbstractServer::with_shutdown).
+/// All clones shut down the same servers.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let server = Server::new(handle_request);
+/// let shutdown = server.shutdown_handle();
+/// tokio::spawn(server.serve(transports));
+///
+/// tokio::signal::ctrl_c().await?;
+/// shutdown.shutdown(tokio::time::sleep(Duration::from_secs(10))).await;
+/// ```
+#[derive(Clone, Default)]
+pub struct ShutdownHandle(Arc<ShutdownInner>);
+
+struct ShutdownInner {
+    stop: Signal,
+    abort: Signal,
+    /// Every running server holds a clone of the sender, so the receiver ends
+    /// once all of them are done. Taken on shutdown.
+    running_tx: Mutex<Option<mpsc::Sender<()>>>,
+    running_rx: lock::Mutex<mpsc::Receiver<()>>,
+}
+
+impl Default for ShutdownInner {
+    fn default() -> Self {
+        let (running_tx, running_rx) = mpsc::channel(0);
+        ShutdownInner {
+            stop: Signal::default(),
+            abort: Signal::default(),
+            running_tx: Mutex::new(Some(running_tx)),
+            running_rx: lock::Mutex::new(running_rx),
+        }
+    }
+}
+
+impl ShutdownHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop accepting new requests, and wait for the requests that are being
+    /// handled. Once `deadline` completes, the handlers that are still running
+    /// are dropped, which responds to their requests with
+    /// [`RequestError::Cancelled`](super::RequestError::Cancelled).
+    ///
+    /// Completes once every server has sent its last response.
+    pub async fn shutdown(&self, deadline: impl Future<Output = ()>) {
+        self.0.stop.fire();
+        self.0.running_tx.lock().unwrap().take();
+        // Only one caller can wait for the servers to finish at a time, so the
+        // first wait has to be over before the second one starts.
+        let finished = match future::select(pin!(self.finished()), pin!(deadline)).await {
+            Either::Left(_) => true,
+            Either::Right(_) => false,
+        };
+        if !finished {
+            self.0.abort.fire();
+            self.finished().await;
+        }
+    }
+
+    /// Whether [`shutdown`](ShutdownHandle::shutdown) has been called.
+    pub fn is_shut_down(&self) -> bool {
+        self.0.stop.is_fired()
+    }
+
+    /// Keeps the shutdown from completing until dropped. `None` if the shutdown
+    /// has already begun.
+    pub(super) fn running(&self) -> Option<mpsc::Sender<()>> {
+        self.0.running_tx.lock().unwrap().clone()
+    }
+
+    /// Completes once servers should stop accepting requests.
+    pub(super) async fn stopped(&self) {
+        self.0.stop.fired().await
+    }
+
+    /// Completes once servers should drop the handlers that are still running.
+    pub(super) async fn aborted(&self) {
+        self.0.abort.fired().await
+    }
+
+    async fn finished(&self) {
+        let mut running_rx = self.0.running_rx.lock().await;
+        while running_rx.next().await.is_some() {}
+    }
+}
+
+/// Fires at most once, and wakes everyone waiting for it.
+struct Signal {
+    tx: Mutex<Option<oneshot::Sender<()>>>,
+    rx: Shared<oneshot::Receiver<()>>,
+}
+
+impl Default for Signal {
+    fn default() -> Self {
+        let (tx, rx) = oneshot::channel();
+        Signal {
+            tx: Mutex::new(Some(tx)),
+            rx: rx.shared(),
+        }
+    }
+}
+
+impl Signal {
+    fn fire(&self) {
+        if let Some(tx) = self.tx.lock().unwrap().take() {
+            tx.send(()).ok();
+        }
+    }
+
+    fn is_fired(&self) -> bool {
+        self.rx.peek().is_some()
+    }
+
+    async fn fired(&self) {
+        // The sender is only dropped after firing.
+        self.rx.clone().await.ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::{AbstractClient, AbstractServer, Request, RequestError, Responder, transport};
+
+    #[test]
+    fn test_shutdown_waits_for_handlers() {
+        let (client_transport, server_transport) = transport();
+        let (client, client_task) = AbstractClient::new(client_transport);
+        let shutdown = ShutdownHandle::new();
+
+        // Responds once it has been released.
+        let (started_tx, mut started_rx) = mpsc::unbounded();
+        let (release_tx, release_rx) = oneshot::channel();
+        let release_rx = release_rx.shared();
+        let handle_request = async |req: Request<u32>, responder: Responder<u32>| {
+            started_tx.unbounded_send(()).unwrap();
+            release_rx.clone().await.unwrap();
+            responder.respond(Ok(req.data)).await;
+        };
+        let server_task =
+            AbstractServer::with_shutdown(server_transport, shutdown.clone(), handle_request);
+
+        let test = async {
+            let shut_down = async {
+                started_rx.next().await;
+                let release = async { release_tx.send(()).unwrap() };
+                future::join(shutdown.shutdown(future::pending()), release).await;
+            };
+            let (res, _) = future::join(client.make_request(1), shut_down).await;
+            assert_eq!(res.unwrap(), 1);
+        };
+
+        block_on(future::join3(test, client_task, server_task));
+    }
+
+    #[test]
+    fn test_shutdown_deadline_cancels_handlers() {
+        let (client_transport, server_transport) = transport();
+        let (client, client_task) = AbstractClient::new(client_transport);
+        let shutdown = ShutdownHandle::new();
+
+        // Never responds.
+        let (started_tx, mut started_rx) = mpsc::unbounded();
+        let handle_request = async |_: Request<u32>, _responder: Responder<u32>| {
+            started_tx.unbounded_send(()).unwrap();
+            future::pending::<()>().await;
+        };
+        let server_task =
+            AbstractServer::with_shutdown(server_transport, shutdown.clone(), handle_request);
+
+        let test = async {
+            let shut_down = async {
+                started_rx.next().await;
+                shutdown.shutdown(future::ready(())).await;
+            };
+            let (res, _) = future::join(client.make_request(1), shut_down).await;
+            assert!(matches!(res, Err(RequestError::Cancelled)));
+            assert!(shutdown.is_shut_down());
+        };
+
+        block_on(future::join3(test, client_task, server_task));
+    }
+}
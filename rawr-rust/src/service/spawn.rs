@@ -0,0 +1,167 @@
+use futures::{FutureExt, future::RemoteHandle};
+
+use super::{Handler, Request, Responder};
+
+/// Runs futures in the background, e.g. as tasks of a multi-threaded runtime.
+///
+/// Implemented for tokio and async-std behind the features of the same name.
+pub trait Spawn {
+    fn spawn(&self, future: impl Future<Output = ()> + Send + 'static);
+}
+
+/// Spawns onto the tokio runtime the server is running in.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioSpawner;
+
+#[cfg(feature = "tokio")]
+impl Spawn for TokioSpawner {
+    fn spawn(&self, future: impl Future<Output = ()> + Send + 'static) {
+        tokio::spawn(future);
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Spawn for tokio::runtime::Handle {
+    fn spawn(&self, future: impl Future<Output = ()> + Send + 'static) {
+        tokio::runtime::Handle::spawn(self, future);
+    }
+}
+
+/// Spawns onto the global async-std executor.
+#[cfg(feature = "async-std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsyncStdSpawner;
+
+#[cfg(feature = "async-std")]
+impl Spawn for AsyncStdSpawner {
+    fn spawn(&self, future: impl Future<Output = ()> + Send + 'static) {
+        async_std::task::spawn(future);
+    }
+}
+
+/// A handler that runs every request as a task of its own, so that requests of
+/// the same connection are handled in parallel, and a slow handler doesn't hold
+/// up the others.
+///
+/// Handlers are usually run by the task of their connection, which works on
+/// single-threaded runtimes and with `!Send` handlers. Spawned handlers instead
+/// have to return a future that is `Send + 'static`, so they take their request
+/// by value and clone whatever they share.
+///
+/// Cancelling a request, or the deadline of a shutdown, drops the spawned task
+/// like any other handler.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let handle_request = move |req, responder| {
+///     let service = service.clone();
+///     async move { TestServer::handle_request(&service, req, responder).await }
+/// };
+///
+/// // With a server.
+/// Server::new(handle_request).spawn_with(TokioSpawner).serve(transports).await;
+///
+/// // On a single connection.
+/// let handler = Spawned::new(TokioSpawner, handle_request);
+/// AbstractServer::new(transport, async |req, responder| handler.handle(req, responder).await).await;
+/// ```
+#[derive(Clone)]
+pub struct Spawned<S, H> {
+    spawner: S,
+    handle_request: H,
+}
+
+impl<S, H> Spawned<S, H> {
+    pub fn new(spawner: S, handle_request: H) -> Self {
+        Spawned {
+            spawner,
+            handle_request,
+        }
+    }
+
+    /// Spawn the handling of `req`. Like [`handle`](Handler::handle), but the
+    /// returned future is known to be `Send`.
+    pub fn spawn<Req, Res, F>(
+        &self,
+        req: Request<Req>,
+        responder: Responder<Res>,
+    ) -> RemoteHandle<()>
+    where
+        S: Spawn,
+        H: Fn(Request<Req>, Responder<Res>) -> F,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let context = req.context.clone();
+        let handler = context.scope((self.handle_request)(req, responder));
+        // Dropping the handle drops the task too.
+        let (task, handle) = handler.remote_handle();
+        self.spawner.spawn(task);
+        handle
+    }
+}
+
+impl<Req, Res, S, H, F> Handler<Req, Res> for Spawned<S, H>
+where
+    S: Spawn,
+    H: Fn(Request<Req>, Responder<Res>) -> F,
+    F: Future<Output = ()> + Send + 'static,
+{
+    async fn handle(&self, req: Request<Req>, responder: Responder<Res>) {
+        self.spawn(req, responder).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::pin,
+        sync::{Arc, Barrier},
+        thread,
+    };
+
+    use futures::{executor::block_on, future};
+
+    use super::*;
+    use crate::{AbstractClient, AbstractServer, transport};
+
+    /// Runs every future on a thread of its own.
+    struct Threads;
+
+    impl Spawn for Threads {
+        fn spawn(&self, future: impl Future<Output = ()> + Send + 'static) {
+            thread::spawn(move || block_on(future));
+        }
+    }
+
+    #[test]
+    fn test_spawned_handlers_run_in_parallel() {
+        let (client_transport, server_transport) = transport();
+        let (client, client_task) = AbstractClient::new(client_transport);
+
+        // Blocks its thread until both requests are being handled, which never
+        // happens if they are handled one after the other.
+        let barrier = Arc::new(Barrier::new(2));
+        let handle_request = move |req: Request<u32>, responder: Responder<u32>| {
+            let barrier = barrier.clone();
+            async move {
+                barrier.wait();
+                responder.respond(Ok(req.data)).await;
+            }
+        };
+        let handler = Spawned::new(Threads, handle_request);
+        let server_task = AbstractServer::new(server_transport, async |req, responder| {
+            handler.handle(req, responder).await
+        });
+
+        let test = async {
+            let (first, second) =
+                future::join(client.make_request(1), client.make_request(2)).await;
+            assert_eq!((first.unwrap(), second.unwrap()), (1, 2));
+        };
+
+        let tasks = future::join(client_task, server_task);
+        block_on(future::select(pin!(test), pin!(tasks)));
+    }
+}
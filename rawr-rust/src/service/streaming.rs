@@ -0,0 +1,307 @@
+use std::{
+    pin::{Pin, pin},
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures::{
+    SinkExt, Stream, StreamExt,
+    channel::mpsc::{self, Sender, UnboundedReceiver, UnboundedSender},
+};
+
+use super::{Envelope, Packet, Pending, RequestError, Result};
+use crate::dashmap::DashMap;
+
+/// Stream of responses to a single request, returned by
+/// [`AbstractClient::make_stream_request`](super::AbstractClient::make_stream_request).
+///
+/// Ends when the server has sent its last item. Dropping it before that cancels
+/// the request, and the server stops producing items.
+pub struct ResponseStream<Res> {
+    /// `None` if the request couldn't be sent, so there is nothing to cancel.
+    pub(super) id: Option<u32>,
+    pub(super) rx: UnboundedReceiver<Result<Res>>,
+    pub(super) requests: Arc<DashMap<u32, Pending<Res>>>,
+    pub(super) cancel_tx: UnboundedSender<u32>,
+}
+
+impl<Res> Stream for ResponseStream<Res> {
+    type Item = Result<Res>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_next_unpin(cx)
+    }
+}
+
+impl<Res> Drop for ResponseStream<Res> {
+    fn drop(&mut self) {
+        // The entry is only still there if the server hasn't ended the stream.
+        if let Some(id) = self.id
+            && self.requests.remove(&id).is_some()
+        {
+            self.cancel_tx.unbounded_send(id).ok();
+        }
+    }
+}
+
+/// Number of chunks of an upload that the server buffers at most. Credit for
+/// half of them is granted again once the handler has consumed them.
+const UPLOAD_WINDOW: u32 = 16;
+
+/// Stream of chunks the client uploads along with a request, see
+/// [`AbstractClient::make_upload_request`](super::AbstractClient::make_upload_request).
+///
+/// Chunks are only sent by the client as the handler consumes them, so an upload
+/// never has to be buffered as a whole. Ends once the client has sent the last
+/// chunk. It is always empty for requests without an upload.
+pub struct UploadStream<Req> {
+    id: u32,
+    rx: UnboundedReceiver<Req>,
+    credit_tx: UnboundedSender<(u32, u32)>,
+    /// Chunks consumed since credit was last granted. `None` until first polled.
+    consumed: Option<u32>,
+}
+
+impl<Req> UploadStream<Req> {
+    pub(super) fn new(
+        id: u32,
+        rx: UnboundedReceiver<Req>,
+        credit_tx: UnboundedSender<(u32, u32)>,
+    ) -> Self {
+        UploadStream {
+            id,
+            rx,
+            credit_tx,
+            consumed: None,
+        }
+    }
+
+    /// Upload stream of a notification, which can't have an upload.
+    pub(super) fn empty() -> Self {
+        let (_, rx) = mpsc::unbounded();
+        let (credit_tx, _) = mpsc::unbounded();
+        UploadStream {
+            id: 0,
+            rx,
+            credit_tx,
+            // Already polled, so that no credit is ever granted.
+            consumed: Some(0),
+        }
+    }
+
+    fn grant(&self, credit: u32) {
+        self.credit_tx.unbounded_send((self.id, credit)).ok();
+    }
+}
+
+impl<Req> Stream for UploadStream<Req> {
+    type Item = Req;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // Nothing is uploaded until the handler asks for it.
+        let consumed = match self.consumed {
+            Some(consumed) => consumed,
+            None => {
+                self.grant(UPLOAD_WINDOW);
+                0
+            }
+        };
+
+        let chunk = self.rx.poll_next_unpin(cx);
+        self.consumed = Some(match chunk {
+            Poll::Ready(Some(_)) if consumed + 1 == UPLOAD_WINDOW / 2 => {
+                self.grant(UPLOAD_WINDOW / 2);
+                0
+            }
+            Poll::Ready(Some(_)) => consumed + 1,
+            _ => consumed,
+        });
+        chunk
+    }
+}
+
+/// Sends the response to a single request. Passed to the request handler of
+/// [`AbstractServer`](super::AbstractServer) along with the request.
+///
+/// If it is dropped without responding, e.g. because the request was cancelled,
+/// the client is notified on a best-effort basis.
+///
+/// Notifications get a responder too, which discards the response.
+pub struct Responder<Res> {
+    id: u32,
+    /// `None` for notifications.
+    tx: Option<Sender<Envelope<Result<Res>>>>,
+    /// Where a response stream is sent instead of `tx`, for requests of a batch.
+    /// Single responses are sent along with the rest of the batch, but a stream
+    /// may never end, so it mustn't hold up the batch.
+    stream_tx: Option<Sender<Envelope<Result<Res>>>>,
+    state: ResponderState,
+}
+
+enum ResponderState {
+    Pending,
+    Streaming,
+    Done,
+}
+
+impl<Res> Responder<Res> {
+    pub(super) fn new(id: u32, tx: Sender<Envelope<Result<Res>>>) -> Self {
+        Responder {
+            id,
+            tx: Some(tx),
+            stream_tx: None,
+            state: ResponderState::Pending,
+        }
+    }
+
+    /// Responder to a request of a batch, whose single response is sent to
+    /// `batch_tx` and whose response stream is sent to `tx`.
+    pub(super) fn batched(
+        id: u32,
+        batch_tx: Sender<Envelope<Result<Res>>>,
+        tx: Sender<Envelope<Result<Res>>>,
+    ) -> Self {
+        Responder {
+            id,
+            tx: Some(batch_tx),
+            stream_tx: Some(tx),
+            state: ResponderState::Pending,
+        }
+    }
+
+    pub(super) fn notification() -> Self {
+        Responder {
+            id: 0,
+            tx: None,
+            stream_tx: None,
+            state: ResponderState::Done,
+        }
+    }
+
+    /// Send a single response.
+    pub async fn respond(mut self, res: Result<Res>) {
+        self.state = ResponderState::Done;
+        let Some(tx) = &mut self.tx else {
+            return;
+        };
+        let packet = Packet::new(self.id, res);
+        // If the client is gone, there is no one left to respond to.
+        tx.send(packet.into()).await.ok();
+    }
+
+    /// Send every item of `items` as a separate response, followed by the
+    /// end-of-stream marker.
+    pub async fn respond_stream(mut self, items: impl Stream<Item = Result<Res>>) {
+        // Leaves the batch, if any, which no longer waits for this response.
+        if let Some(stream_tx) = self.stream_tx.take() {
+            self.tx = Some(stream_tx);
+        }
+        let Some(tx) = &mut self.tx else {
+            return;
+        };
+        self.state = ResponderState::Streaming;
+        let mut items = pin!(items);
+        while let Some(item) = items.next().await {
+            let packet = Packet::new(self.id, item);
+            if tx.send(packet.into()).await.is_err() {
+                self.state = ResponderState::Done;
+                return;
+            }
+        }
+        self.state = ResponderState::Done;
+        tx.send(Envelope::End(self.id)).await.ok();
+    }
+}
+
+impl<Res> Drop for Responder<Res> {
+    fn drop(&mut self) {
+        let Some(tx) = &mut self.tx else {
+            return;
+        };
+        let envelope = match self.state {
+            ResponderState::Pending => {
+                Envelope::Packet(Packet::new(self.id, Err(RequestError::Cancelled)))
+            }
+            ResponderState::Streaming => Envelope::End(self.id),
+            ResponderState::Done => return,
+        };
+        // NOTE: Every sender has a slot of its own in the channel, so this only
+        // fails if this responder has already sent something that hasn't been
+        // picked up yet.
+        tx.try_send(envelope).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Mutex,
+        atomic::{AtomicU32, Ordering},
+    };
+
+    use futures::{channel::oneshot, executor::block_on, future, stream};
+
+    use super::*;
+    use crate::{AbstractClient, AbstractServer, Request, transport};
+
+    #[test]
+    fn test_dropping_response_stream_cancels_handler() {
+        let (client_transport, server_transport) = transport();
+        let (client, client_task) = AbstractClient::new(client_transport);
+
+        // Resolves once the handler future is dropped.
+        let (dropped_tx, dropped_rx) = oneshot::channel::<()>();
+        let dropped_tx = Mutex::new(Some(dropped_tx));
+        let handle_request = async |req: Request<u32>, responder: Responder<u32>| {
+            let _dropped_tx = dropped_tx.lock().unwrap().take();
+            let items = stream::iter(req.data..).map(Ok);
+            responder.respond_stream(items).await;
+        };
+        let server_task = AbstractServer::new(server_transport, handle_request);
+
+        let test = async {
+            let items = client.make_stream_request(10).await;
+            let items: Vec<_> = items.take(3).map(Result::unwrap).collect().await;
+            assert_eq!(items, [10, 11, 12]);
+
+            // The stream was dropped by `take`, so the handler should be too.
+            dropped_rx.await.ok();
+        };
+
+        let tasks = future::join(client_task, server_task);
+        block_on(future::select(pin!(test), pin!(tasks)));
+    }
+
+    #[test]
+    fn test_upload_is_flow_controlled() {
+        let (client_transport, server_transport) = transport();
+        let (client, client_task) = AbstractClient::new(client_transport);
+
+        // Sum up the chunks, checking that the client never gets further ahead
+        // than the upload window.
+        let sent = AtomicU32::new(0);
+        let handle_request = async |req: Request<u32>, responder: Responder<u32>| {
+            let mut uploads = req.uploads;
+            let mut sum = 0;
+            let mut received = 0;
+            while let Some(chunk) = uploads.next().await {
+                received += 1;
+                assert!(sent.load(Ordering::SeqCst) - received <= UPLOAD_WINDOW);
+                sum += chunk;
+            }
+            responder.respond(Ok(sum)).await;
+        };
+        let server_task = AbstractServer::new(server_transport, handle_request);
+
+        let test = async {
+            let chunks = stream::iter(1..=100).inspect(|_| {
+                sent.fetch_add(1, Ordering::SeqCst);
+            });
+            let sum = client.make_upload_request(0, chunks).await.unwrap();
+            assert_eq!(sum, 5050);
+        };
+
+        let tasks = future::join(client_task, server_task);
+        block_on(future::select(pin!(test), pin!(tasks)));
+    }
+}
@@ -0,0 +1,13 @@
+//! Transports that carry [`Packet`](crate::Packet)s over a real connection.
+//!
+//! Every transport is a `Sink`/`Stream` pair that can be passed directly to
+//! [`AbstractClient::new`](crate::AbstractClient::new) or
+//! [`AbstractServer::new`](crate::AbstractServer::new). For an in-memory
+//! transport, see [`transport()`](crate::transport()).
+
+#[cfg(feature = "stdio")]
+pub mod stdio;
+#[cfg(feature = "stream")]
+pub mod stream;
+#[cfg(feature = "websocket")]
+pub mod websocket;
@@ -0,0 +1,203 @@
+//! Transport over standard input/output, for running a rawr service as a child
+//! process of its client (similar to how language servers work).
+//!
+//! The child serves requests read from its stdin and writes responses to its
+//! stdout, so it must not print anything else to stdout. Logs should go to
+//! stderr instead.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! // Plugin (child process)
+//! let transport = rawr::transport::stdio::server(Json, Framing::Lines);
+//! TestServer::new(transport, ServiceImpl).await;
+//!
+//! // Host (parent process)
+//! let mut plugin = Command::new("plugin")
+//!     .stdin(Stdio::piped())
+//!     .stdout(Stdio::piped())
+//!     .spawn()?;
+//! let transport = rawr::transport::stdio::client(&mut plugin, Json, Framing::Lines);
+//! let (client, client_task) = TestClient::new(transport);
+//! ```
+
+use std::io;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::{Sink, Stream};
+use serde::{Serialize, de::DeserializeOwned};
+use tokio::{
+    io::{stdin, stdout},
+    process::Child,
+};
+use tokio_util::codec::{Decoder, Encoder, FramedRead, FramedWrite};
+
+use super::stream::{sink, stream};
+use crate::{Envelope, Result, TransportError, codec::Codec};
+
+/// How packets are delimited on the pipe.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Framing {
+    /// One packet per line. Only works with codecs that never produce a newline,
+    /// such as [`Json`](crate::codec::Json).
+    #[default]
+    Lines,
+    /// Every packet is preceded by a `Content-Length: <bytes>\r\n\r\n` header,
+    /// like in the Language Server Protocol. Works with any codec.
+    ContentLength,
+}
+
+/// Server transport over the stdin/stdout of the current process.
+pub fn server<Req, Res>(
+    codec: impl Codec,
+    framing: Framing,
+) -> (
+    impl Stream<Item = Envelope<Req>>,
+    impl Sink<Envelope<Result<Res>>, Error = TransportError>,
+)
+where
+    Req: DeserializeOwned,
+    Res: Serialize,
+{
+    let rx = FramedRead::new(stdin(), FrameCodec(framing));
+    let tx = FramedWrite::new(stdout(), FrameCodec(framing));
+    (stream(rx, codec.clone()), sink(tx, codec))
+}
+
+/// Client transport over the stdin/stdout pipes of a spawned `child`.
+///
+/// ## Panics
+///
+/// Panics if the child wasn't spawned with both stdin and stdout piped, or if
+/// they have already been taken.
+pub fn client<Req, Res, C: Codec>(
+    child: &mut Child,
+    codec: C,
+    framing: Framing,
+) -> (
+    impl Sink<Envelope<Req>, Error = TransportError> + use<Req, Res, C>,
+    impl Stream<Item = Envelope<Result<Res>>> + use<Req, Res, C>,
+)
+where
+    Req: Serialize,
+    Res: DeserializeOwned,
+{
+    let stdin = child.stdin.take().expect("child stdin must be piped");
+    let stdout = child.stdout.take().expect("child stdout must be piped");
+    let tx = FramedWrite::new(stdin, FrameCodec(framing));
+    let rx = FramedRead::new(stdout, FrameCodec(framing));
+    (sink(tx, codec.clone()), stream(rx, codec))
+}
+
+struct FrameCodec(Framing);
+
+const CONTENT_LENGTH: &str = "content-length:";
+
+impl Decoder for FrameCodec {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+        match self.0 {
+            Framing::Lines => loop {
+                let Some(end) = src.iter().position(|b| *b == b'\n') else {
+                    return Ok(None);
+                };
+                let mut line = src.split_to(end + 1);
+                line.truncate(line.trim_ascii_end().len());
+                // Skip blank lines.
+                if !line.is_empty() {
+                    return Ok(Some(line));
+                }
+            },
+            Framing::ContentLength => {
+                let Some(end) = src.windows(4).position(|w| w == b"\r\n\r\n") else {
+                    return Ok(None);
+                };
+                let headers = String::from_utf8_lossy(&src[..end]);
+                let length = headers
+                    .lines()
+                    .find_map(|header| {
+                        let (name, value) = header.split_at_checked(CONTENT_LENGTH.len())?;
+                        name.eq_ignore_ascii_case(CONTENT_LENGTH)
+                            .then(|| value.trim().parse::<usize>().ok())?
+                    })
+                    .ok_or_else(|| invalid_data("missing or invalid Content-Length header"))?;
+
+                let body_start = end + 4;
+                if src.len() < body_start + length {
+                    src.reserve(body_start + length - src.len());
+                    return Ok(None);
+                }
+                src.advance(body_start);
+                Ok(Some(src.split_to(length)))
+            }
+        }
+    }
+}
+
+impl Encoder<Bytes> for FrameCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> io::Result<()> {
+        match self.0 {
+            Framing::Lines => {
+                if item.contains(&b'\n') {
+                    return Err(invalid_data("packet contains a newline"));
+                }
+                dst.reserve(item.len() + 1);
+                dst.put(item);
+                dst.put_u8(b'\n');
+            }
+            Framing::ContentLength => {
+                let header = format!("Content-Length: {}\r\n\r\n", item.len());
+                dst.reserve(header.len() + item.len());
+                dst.put(header.as_bytes());
+                dst.put(item);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(framing: Framing) {
+        let mut codec = FrameCodec(framing);
+        let mut buf = BytesMut::new();
+        codec
+            .encode(Bytes::from_static(b"{\"id\":0}"), &mut buf)
+            .unwrap();
+        codec
+            .encode(Bytes::from_static(b"{\"id\":1}"), &mut buf)
+            .unwrap();
+
+        // Feed the frames in byte by byte, as if they arrived in small chunks.
+        let mut src = BytesMut::new();
+        let mut frames = Vec::new();
+        for byte in buf {
+            src.put_u8(byte);
+            while let Some(frame) = codec.decode(&mut src).unwrap() {
+                frames.push(frame);
+            }
+        }
+
+        assert_eq!(frames, [&b"{\"id\":0}"[..], &b"{\"id\":1}"[..]]);
+    }
+
+    #[test]
+    fn test_lines_roundtrip() {
+        roundtrip(Framing::Lines);
+    }
+
+    #[test]
+    fn test_content_length_roundtrip() {
+        roundtrip(Framing::ContentLength);
+    }
+}
@@ -0,0 +1,122 @@
+//! Transport over any byte stream, such as a TCP or Unix domain socket. Each
+//! packet is encoded with a [`Codec`] and prefixed with its length as a 32-bit
+//! big-endian integer.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! // Server
+//! let (socket, _) = listener.accept().await?;
+//! let transport = rawr::transport::stream::server(socket, Json);
+//! TestServer::new(transport, ServiceImpl).await;
+//!
+//! // Client
+//! let socket = TcpStream::connect(addr).await?;
+//! let (client, client_task) = TestClient::new(rawr::transport::stream::client(socket, Json));
+//! ```
+
+use std::future;
+
+use bytes::{Bytes, BytesMut};
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use serde::{Serialize, de::DeserializeOwned};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+use crate::{Envelope, PeerMessage, Result, TransportError, codec::Codec};
+
+/// Client transport over `io`. Drop-in replacement for the client half of
+/// [`transport()`](crate::transport()).
+pub fn client<Req, Res>(
+    io: impl AsyncRead + AsyncWrite,
+    codec: impl Codec,
+) -> (
+    impl Sink<Envelope<Req>, Error = TransportError>,
+    impl Stream<Item = Envelope<Result<Res>>>,
+)
+where
+    Req: Serialize,
+    Res: DeserializeOwned,
+{
+    let (tx, rx) = Framed::new(io, LengthDelimitedCodec::new()).split();
+    (sink(tx, codec.clone()), stream(rx, codec))
+}
+
+/// Server transport over `io`. Drop-in replacement for the server half of
+/// [`transport()`](crate::transport()).
+pub fn server<Req, Res>(
+    io: impl AsyncRead + AsyncWrite,
+    codec: impl Codec,
+) -> (
+    impl Stream<Item = Envelope<Req>>,
+    impl Sink<Envelope<Result<Res>>, Error = TransportError>,
+)
+where
+    Req: DeserializeOwned,
+    Res: Serialize,
+{
+    let (tx, rx) = Framed::new(io, LengthDelimitedCodec::new()).split();
+    (stream(rx, codec.clone()), sink(tx, codec))
+}
+
+/// Transport over `io` for an [`AbstractPeer`](crate::AbstractPeer), which
+/// serves requests and makes requests of its own over the same connection.
+pub fn peer<Req, Res, PeerReq, PeerRes>(
+    io: impl AsyncRead + AsyncWrite,
+    codec: impl Codec,
+) -> (
+    impl Sink<PeerMessage<PeerReq, Res>, Error = TransportError>,
+    impl Stream<Item = PeerMessage<Req, PeerRes>>,
+)
+where
+    Req: DeserializeOwned,
+    Res: Serialize,
+    PeerReq: Serialize,
+    PeerRes: DeserializeOwned,
+{
+    let (tx, rx) = Framed::new(io, LengthDelimitedCodec::new()).split();
+    (sink(tx, codec.clone()), stream(rx, codec))
+}
+
+pub(super) fn sink<P: Serialize>(
+    tx: impl Sink<Bytes, Error = std::io::Error>,
+    codec: impl Codec,
+) -> impl Sink<P, Error = TransportError> {
+    let tx = tx.sink_map_err(|e| {
+        log::error!("Failed to write frame: {}", e);
+        TransportError::SendError
+    });
+    tx.with(move |packet: P| {
+        future::ready(match codec.encode(&packet) {
+            Ok(bytes) => Ok(Bytes::from(bytes)),
+            Err(e) => {
+                log::error!("Failed to serialize packet: {}", e);
+                Err(TransportError::SendError)
+            }
+        })
+    })
+}
+
+pub(super) fn stream<P: DeserializeOwned>(
+    rx: impl Stream<Item = std::io::Result<BytesMut>>,
+    codec: impl Codec,
+) -> impl Stream<Item = P> {
+    let rx = rx.take_while(|frame| {
+        if let Err(e) = frame {
+            log::error!("Failed to read frame: {}", e);
+        }
+        future::ready(frame.is_ok())
+    });
+    rx.filter_map(move |frame| future::ready(frame.ok().and_then(|frame| decode(&codec, &frame))))
+}
+
+/// Malformed packets are logged and skipped, the framing itself is still intact.
+fn decode<P: DeserializeOwned>(codec: &impl Codec, bytes: &[u8]) -> Option<P> {
+    match codec.decode(bytes) {
+        Ok(packet) => Some(packet),
+        Err(e) => {
+            log::error!("Failed to deserialize packet: {}", e);
+            None
+        }
+    }
+}
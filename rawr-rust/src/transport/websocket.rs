@@ -0,0 +1,223 @@
+//! WebSocket transport. By default packets are sent as JSON text frames, the
+//! same framing `RpcClient` from `rawr-typescript` uses, so Rust and TypeScript
+//! peers can talk to each other. Rust-only deployments can pick a more compact
+//! [`Codec`] with [`connect_with_codec`] and [`accept_with_codec`], in which
+//! case packets are sent as binary frames.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let listener = TcpListener::bind(addr).await?;
+//! let (stream, _) = listener.accept().await?;
+//! let transport = rawr::transport::websocket::accept(stream).await?;
+//! TestServer::new(transport, ServiceImpl).await;
+//! ```
+
+use std::future;
+
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use serde::{Serialize, de::DeserializeOwned};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_tungstenite::{
+    accept_async, connect_async,
+    tungstenite::{Error, Message, error::ProtocolError},
+};
+
+use crate::{
+    Envelope, PeerMessage, Result, TransportError,
+    codec::{Codec, Json},
+};
+
+pub use tokio_tungstenite;
+
+/// Connect to a rawr server at `url` (e.g. `ws://127.0.0.1:5555`) and return a
+/// client transport for it.
+pub async fn connect<Req, Res>(
+    url: &str,
+) -> std::result::Result<
+    (
+        impl Sink<Envelope<Req>, Error = TransportError> + use<Req, Res>,
+        impl Stream<Item = Envelope<Result<Res>>> + use<Req, Res>,
+    ),
+    Error,
+>
+where
+    Req: Serialize,
+    Res: DeserializeOwned,
+{
+    connect_with_codec(url, Json).await
+}
+
+/// Like [`connect`], but encodes packets with `codec` instead of JSON.
+pub async fn connect_with_codec<Req, Res, C: Codec>(
+    url: &str,
+    codec: C,
+) -> std::result::Result<
+    (
+        impl Sink<Envelope<Req>, Error = TransportError> + use<Req, Res, C>,
+        impl Stream<Item = Envelope<Result<Res>>> + use<Req, Res, C>,
+    ),
+    Error,
+>
+where
+    Req: Serialize,
+    Res: DeserializeOwned,
+{
+    let (ws, _) = connect_async(url).await?;
+    let (tx, rx) = ws.split();
+    Ok((sink(tx, codec.clone()), stream(rx, codec)))
+}
+
+/// Perform the WebSocket handshake over an accepted connection and return a
+/// server transport for it.
+pub async fn accept<Req, Res, S>(
+    stream: S,
+) -> std::result::Result<
+    (
+        impl Stream<Item = Envelope<Req>>,
+        impl Sink<Envelope<Result<Res>>, Error = TransportError>,
+    ),
+    Error,
+>
+where
+    Req: DeserializeOwned,
+    Res: Serialize,
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    accept_with_codec(stream, Json).await
+}
+
+/// Like [`accept`], but encodes packets with `codec` instead of JSON.
+pub async fn accept_with_codec<Req, Res, S>(
+    stream: S,
+    codec: impl Codec,
+) -> std::result::Result<
+    (
+        impl Stream<Item = Envelope<Req>>,
+        impl Sink<Envelope<Result<Res>>, Error = TransportError>,
+    ),
+    Error,
+>
+where
+    Req: DeserializeOwned,
+    Res: Serialize,
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let ws = accept_async(stream).await?;
+    let (tx, rx) = ws.split();
+    Ok((self::stream(rx, codec.clone()), sink(tx, codec)))
+}
+
+/// Like [`connect_with_codec`], but for an [`AbstractPeer`](crate::AbstractPeer),
+/// which serves requests of the other end as well as making its own.
+pub async fn connect_peer<Req, Res, PeerReq, PeerRes, C: Codec>(
+    url: &str,
+    codec: C,
+) -> std::result::Result<
+    (
+        impl Sink<PeerMessage<PeerReq, Res>, Error = TransportError>
+        + use<Req, Res, PeerReq, PeerRes, C>,
+        impl Stream<Item = PeerMessage<Req, PeerRes>> + use<Req, Res, PeerReq, PeerRes, C>,
+    ),
+    Error,
+>
+where
+    Req: DeserializeOwned,
+    Res: Serialize,
+    PeerReq: Serialize,
+    PeerRes: DeserializeOwned,
+{
+    let (ws, _) = connect_async(url).await?;
+    let (tx, rx) = ws.split();
+    Ok((sink(tx, codec.clone()), stream(rx, codec)))
+}
+
+/// Like [`accept_with_codec`], but for an [`AbstractPeer`](crate::AbstractPeer),
+/// which serves requests of the other end as well as making its own.
+pub async fn accept_peer<Req, Res, PeerReq, PeerRes, S>(
+    stream: S,
+    codec: impl Codec,
+) -> std::result::Result<
+    (
+        impl Sink<PeerMessage<PeerReq, Res>, Error = TransportError>,
+        impl Stream<Item = PeerMessage<Req, PeerRes>>,
+    ),
+    Error,
+>
+where
+    Req: DeserializeOwned,
+    Res: Serialize,
+    PeerReq: Serialize,
+    PeerRes: DeserializeOwned,
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let ws = accept_async(stream).await?;
+    let (tx, rx) = ws.split();
+    Ok((sink(tx, codec.clone()), self::stream(rx, codec)))
+}
+
+fn sink<P: Serialize>(
+    tx: impl Sink<Message, Error = Error>,
+    codec: impl Codec,
+) -> impl Sink<P, Error = TransportError> {
+    let tx = tx.sink_map_err(|e| {
+        log::error!("Failed to send WebSocket message: {}", e);
+        TransportError::SendError
+    });
+    tx.with(move |packet: P| {
+        future::ready(match codec.encode(&packet) {
+            Ok(bytes) => {
+                log::debug!("Sending message: {}", String::from_utf8_lossy(&bytes));
+                if codec.is_text() {
+                    Ok(String::from_utf8(bytes)
+                        .map(Message::text)
+                        .unwrap_or_else(|e| Message::binary(e.into_bytes())))
+                } else {
+                    Ok(Message::binary(bytes))
+                }
+            }
+            Err(e) => {
+                log::error!("Failed to serialize packet: {}", e);
+                Err(TransportError::SendError)
+            }
+        })
+    })
+}
+
+fn stream<P: DeserializeOwned>(
+    rx: impl Stream<Item = std::result::Result<Message, Error>>,
+    codec: impl Codec,
+) -> impl Stream<Item = P> {
+    let rx = rx.take_while(|msg| {
+        future::ready(match msg {
+            Ok(Message::Close(_)) => false,
+            Err(Error::Protocol(ProtocolError::ResetWithoutClosingHandshake)) => false,
+            Err(e) => {
+                log::error!("Failed to receive WebSocket message: {}", e);
+                false
+            }
+            Ok(_) => true,
+        })
+    });
+    rx.filter_map(move |msg| {
+        future::ready(match msg {
+            Ok(Message::Text(text)) => decode(&codec, text.as_bytes()),
+            Ok(Message::Binary(bytes)) => decode(&codec, &bytes),
+            // Pings are answered by tungstenite itself.
+            _ => None,
+        })
+    })
+}
+
+/// Malformed frames are logged and skipped, instead of taking the whole
+/// connection down.
+fn decode<P: DeserializeOwned>(codec: &impl Codec, bytes: &[u8]) -> Option<P> {
+    log::debug!("Received message: {}", String::from_utf8_lossy(bytes));
+    match codec.decode(bytes) {
+        Ok(packet) => Some(packet),
+        Err(e) => {
+            log::error!("Failed to deserialize packet: {}", e);
+            None
+        }
+    }
+}
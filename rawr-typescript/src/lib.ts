@@ -1,14 +1,76 @@
 type ReqId = number;
 
+/**
+ * Metadata sent along with a packet, like the headers of an HTTP request: trace
+ * ids, auth tokens, locales or deadlines.
+ */
+export type Meta = Record<string, string>;
+
 export interface Packet<T> {
   id: ReqId;
   data: T;
+  /** Left out when there is no metadata. */
+  meta?: Meta;
 }
 
+/**
+ * Everything a client and a server send to each other. Streamed responses and
+ * uploads end with `{ id, end: true }`, and clients cancel requests with
+ * `{ id, cancel: true }`. Upload chunks are sent as `{ id, chunk }`, but only as
+ * many as the server has granted with `{ id, credit }`. Notifications are sent
+ * as `{ notification }`, without an id, and never get a response. Several
+ * packets can be sent at once as `{ batch: [packet, ...] }`, and a batch of
+ * requests is answered with a batch of responses. Either end may send
+ * `{ ping: true }` to check that the other end is still there, which answers
+ * with `{ pong: true }`. A client may send a `{ hello }` first thing after
+ * connecting, which the server answers with its own.
+ */
+export type Envelope<T> =
+  | Packet<T>
+  | { id: ReqId; chunk: T; meta?: Meta }
+  | { id: ReqId; end: true }
+  | { id: ReqId; credit: number }
+  | { id: ReqId; cancel: true }
+  | { notification: T; meta?: Meta }
+  | { batch: Packet<T>[] }
+  | { ping: true }
+  | { pong: true }
+  | { hello: Hello };
+
+/** Version of the protocol, the same as `PROTOCOL_VERSION` of the Rust crate. */
+export const PROTOCOL_VERSION = 1;
+
+/**
+ * What a peer speaks: the version of the protocol, and the fingerprint of its
+ * service, e.g. `TEST_FINGERPRINT` of the generated bindings.
+ */
+export type Hello = { version: number; schema: string };
+
+/**
+ * Method every generated server answers with the description of its service,
+ * the same as `DESCRIBE_METHOD` of the Rust crate.
+ */
+export const DESCRIBE_METHOD = "rawr.describe";
+
+/**
+ * Everything two peers send to each other, when both ends serve a service and
+ * call the service of the other end over a single connection. Each direction
+ * numbers its requests on its own.
+ */
+export type PeerMessage<Req, Res> =
+  | { request: Envelope<Req> }
+  | { response: Envelope<Result<Res>> };
+
 export type HandleRequest<Req, Res> = (
   req: Packet<Req>
 ) => Promise<Packet<Res>>;
 
+/** Handles a notification, which gets no response. */
+export type HandleNotification<Req> = (
+  notification: Req,
+  meta?: Meta
+) => void | Promise<void>;
+
 export type Result<T> = { Ok: T } | { Err: any };
 
 export class RpcClient<Req, Res> {
@@ -17,21 +79,116 @@ export class RpcClient<Req, Res> {
     { resolve: (value: Res) => void; reject: (reason: any) => void }
   > = new Map();
   private nextId: ReqId = 0;
+  /** Requests waiting to be sent in a batch, if one is being collected. */
+  private batched?: Packet<Req>[];
+  /** Metadata sent along with every request, e.g. an auth token. */
+  meta: Meta = {};
+  /** Fingerprint sent with `hello`, if it was. */
+  private schema?: string;
 
-  constructor(private sendRequest: (packet: Packet<Req>) => void) {}
+  /**
+   * If `batchWindow` is set, requests made within that many milliseconds of the
+   * first one are sent in a single batch.
+   */
+  constructor(
+    private sendRequest: (envelope: Envelope<Req>) => void,
+    private batchWindow?: number
+  ) {}
 
-  request(method: string, payload: any): Promise<Res> {
+  /** Make a request. `meta` is sent on top of the metadata of the client. */
+  request(method: string, payload: any, meta?: Meta): Promise<Res> {
     const id = this.nextId++;
     const packet: Packet<Req> = { id, data: { method, payload } } as any;
+    const merged = { ...this.meta, ...meta };
+    if (Object.keys(merged).length > 0) packet.meta = merged;
 
     return new Promise((resolve, reject) => {
       this.pendingRequests.set(id, { resolve, reject });
-      this.sendRequest(packet);
+      this.enqueue(packet);
     });
   }
 
-  handleResponse(packet: Packet<Result<Res>>): void {
-    const { id, data } = packet;
+  /** Send all requests made synchronously within `fn` in a single batch. */
+  batch<T>(fn: () => T): T {
+    const outer = this.batched;
+    this.batched = outer ?? [];
+    try {
+      return fn();
+    } finally {
+      // A batch window that is already open sends these along with its own.
+      if (!outer) this.flush();
+    }
+  }
+
+  private enqueue(packet: Packet<Req>): void {
+    if (this.batched) {
+      this.batched.push(packet);
+    } else if (this.batchWindow === undefined) {
+      this.sendRequest(packet);
+    } else {
+      this.batched = [packet];
+      setTimeout(() => this.flush(), this.batchWindow);
+    }
+  }
+
+  private flush(): void {
+    const packets = this.batched ?? [];
+    this.batched = undefined;
+    if (packets.length === 1) {
+      this.sendRequest(packets[0]);
+    } else if (packets.length > 1) {
+      this.sendRequest({ batch: packets });
+    }
+  }
+
+  /**
+   * Tell the server which service this client speaks. A server that rejects it
+   * responds to every request with an `Incompatible` error, and a mismatch is
+   * logged either way.
+   */
+  hello(schema: string): void {
+    this.schema = schema;
+    this.sendRequest({ hello: { version: PROTOCOL_VERSION, schema } });
+  }
+
+  /**
+   * Send a request without waiting for a response. `meta` is sent on top of the
+   * metadata of the client.
+   */
+  notify(method: string, payload: any, meta?: Meta): void {
+    const notification: Req = { method, payload } as any;
+    const envelope: { notification: Req; meta?: Meta } = { notification };
+    const merged = { ...this.meta, ...meta };
+    if (Object.keys(merged).length > 0) envelope.meta = merged;
+    this.sendRequest(envelope);
+  }
+
+  handleResponse(envelope: Envelope<Result<Res>>): void {
+    if ("ping" in envelope) {
+      this.sendRequest({ pong: true });
+      return;
+    }
+
+    if ("hello" in envelope) {
+      const { version, schema } = envelope.hello;
+      if (version !== PROTOCOL_VERSION || schema !== this.schema) {
+        console.warn(
+          `Server speaks protocol ${version} and service ${schema}, ` +
+            `expected ${PROTOCOL_VERSION} and ${this.schema}`
+        );
+      }
+      return;
+    }
+
+    if ("batch" in envelope) {
+      for (const packet of envelope.batch) this.handleResponse(packet);
+      return;
+    }
+
+    // Streamed responses are not supported yet, so there's nothing to end.
+    if (!("data" in envelope)) return;
+
+    const { id, data } = envelope;
     const pending = this.pendingRequests.get(id);
 
     if (!pending) return;
@@ -52,3 +209,55 @@ export class RpcClient<Req, Res> {
     this.pendingRequests.clear();
   }
 }
+
+/**
+ * One end of a peer connection. Serves requests of the other end with
+ * `handleRequest` and its notifications with `handleNotification`, and calls
+ * the service of the other end through `client`.
+ */
+export class RpcPeer<Req, Res, PeerReq, PeerRes> {
+  readonly client: RpcClient<PeerReq, PeerRes>;
+
+  constructor(
+    private handleRequest: HandleRequest<Req, Result<Res>>,
+    private sendMessage: (message: PeerMessage<PeerReq, Res>) => void,
+    private handleNotification?: HandleNotification<Req>
+  ) {
+    this.client = new RpcClient((request) => sendMessage({ request }));
+  }
+
+  async handleMessage(message: PeerMessage<Req, PeerRes>): Promise<void> {
+    if ("response" in message) {
+      this.client.handleResponse(message.response);
+      return;
+    }
+
+    const request = message.request;
+    if ("ping" in request) {
+      this.sendMessage({ response: { pong: true } });
+      return;
+    }
+
+    if ("batch" in request) {
+      const responses = await Promise.all(request.batch.map(this.handleRequest));
+      this.sendMessage({ response: { batch: responses } });
+      return;
+    }
+
+    if ("notification" in request) {
+      if (!this.handleNotification) {
+        console.warn(
+          "Dropped a notification, as the peer has no handler for them"
+        );
+        return;
+      }
+      await this.handleNotification(request.notification, request.meta);
+      return;
+    }
+
+    // Requests are answered as a whole, so there's nothing to cancel.
+    if (!("data" in request)) return;
+    const response = await this.handleRequest(request);
+    this.sendMessage({ response });
+  }
+}
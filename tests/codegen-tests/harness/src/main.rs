@@ -32,3 +32,47 @@ fn main() {
     log::info!("Comparing bindings with snapshot...");
     diff::compare_directories(expected_path, generated_path).unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rawr::codegen::typescript::Codegen;
+    use schemas::service::TestServer;
+    use walkdir::WalkDir;
+
+    #[test]
+    fn test_described_bindings_match_snapshot() {
+        let expected_path = format!(
+            "{}/../snapshots/typescript-expected",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let described_dir = tempfile::tempdir().unwrap();
+        let described_path = described_dir.path();
+
+        // A client generated from what a server describes has the same types as
+        // one generated from the compiled-in schemas.
+        Codegen::new()
+            .export_description(&TestServer::description())
+            .export_to(described_path)
+            .run();
+
+        let mut compared = 0;
+        for entry in WalkDir::new(described_path) {
+            let entry = entry.unwrap();
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let relative_path = entry.path().strip_prefix(described_path).unwrap();
+            let expected =
+                fs::read_to_string(format!("{}/{}", expected_path, relative_path.display()))
+                    .unwrap_or_else(|_| {
+                        panic!("{} isn't in the snapshot", relative_path.display())
+                    });
+            let described = fs::read_to_string(entry.path()).unwrap();
+            assert_eq!(described, expected, "{} differs", relative_path.display());
+            compared += 1;
+        }
+        assert!(compared > 0);
+    }
+}
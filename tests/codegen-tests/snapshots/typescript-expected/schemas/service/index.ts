@@ -0,0 +1,815 @@
+export const TEST_FINGERPRINT = "ab5213ac308583ac";
+export const TEST_SERVICE = {
+  "name": "TestService",
+  "fingerprint": "ab5213ac308583ac",
+  "methods": [
+    {
+      "name": "say_hello",
+      "kind": "Request",
+      "params": [
+        {
+          "name": "arg",
+          "schema": {
+            "Primitive": "String"
+          }
+        }
+      ],
+      "returns": {
+        "Primitive": "String"
+      }
+    },
+    {
+      "name": "complex",
+      "kind": "Request",
+      "params": [
+        {
+          "name": "input",
+          "schema": {
+            "Named": {
+              "name": "Structure",
+              "module_path": "schemas::structure",
+              "params": []
+            }
+          }
+        },
+        {
+          "name": "n",
+          "schema": {
+            "Primitive": "i32"
+          }
+        }
+      ],
+      "returns": {
+        "Named": {
+          "name": "Structure",
+          "module_path": "schemas::structure",
+          "params": []
+        }
+      }
+    },
+    {
+      "name": "ping_enum",
+      "kind": "Request",
+      "params": [
+        {
+          "name": "arg",
+          "schema": {
+            "Named": {
+              "name": "EnumAdjacentlyTagged",
+              "module_path": "schemas::enumeration",
+              "params": []
+            }
+          }
+        }
+      ],
+      "returns": {
+        "Named": {
+          "name": "EnumAdjacentlyTagged",
+          "module_path": "schemas::enumeration",
+          "params": []
+        }
+      }
+    },
+    {
+      "name": "count_to",
+      "kind": "Stream",
+      "params": [
+        {
+          "name": "n",
+          "schema": {
+            "Primitive": "u32"
+          }
+        }
+      ],
+      "returns": {
+        "Primitive": "u32"
+      }
+    },
+    {
+      "name": "sum",
+      "kind": "Upload",
+      "params": [
+        {
+          "name": "numbers",
+          "schema": {
+            "Primitive": "i32"
+          }
+        }
+      ],
+      "returns": {
+        "Primitive": "i32"
+      }
+    },
+    {
+      "name": "log_event",
+      "kind": "Notification",
+      "params": [
+        {
+          "name": "event",
+          "schema": {
+            "Primitive": "String"
+          }
+        }
+      ],
+      "returns": {
+        "Primitive": "()"
+      }
+    }
+  ],
+  "types": [
+    {
+      "name": "ImportedStruct",
+      "module_path": "schemas::module",
+      "params": [],
+      "definition": {
+        "Struct": {
+          "Map": [
+            {
+              "name": "value",
+              "schema": {
+                "Primitive": "String"
+              }
+            }
+          ]
+        }
+      }
+    },
+    {
+      "name": "EnumAdjacentlyTagged",
+      "module_path": "schemas::enumeration",
+      "params": [],
+      "definition": {
+        "Enum": {
+          "representation": {
+            "Adjacent": {
+              "tag": "type",
+              "content": "data"
+            }
+          },
+          "variants": [
+            {
+              "name": "VariantA",
+              "shape": "Unit"
+            },
+            {
+              "name": "VariantB",
+              "shape": {
+                "Tuple": []
+              }
+            },
+            {
+              "name": "VariantC",
+              "shape": {
+                "Newtype": {
+                  "Primitive": "i32"
+                }
+              }
+            },
+            {
+              "name": "VariantD",
+              "shape": {
+                "Newtype": {
+                  "Primitive": "()"
+                }
+              }
+            },
+            {
+              "name": "VariantE",
+              "shape": {
+                "Newtype": {
+                  "Named": {
+                    "name": "ImportedStruct",
+                    "module_path": "schemas::module",
+                    "params": []
+                  }
+                }
+              }
+            },
+            {
+              "name": "VariantF",
+              "shape": {
+                "Newtype": {
+                  "Tuple": [
+                    {
+                      "Primitive": "i32"
+                    },
+                    {
+                      "Named": {
+                        "name": "ImportedStruct",
+                        "module_path": "schemas::module",
+                        "params": []
+                      }
+                    }
+                  ]
+                }
+              }
+            },
+            {
+              "name": "VariantG",
+              "shape": {
+                "Tuple": [
+                  {
+                    "Primitive": "i32"
+                  },
+                  {
+                    "Named": {
+                      "name": "ImportedStruct",
+                      "module_path": "schemas::module",
+                      "params": []
+                    }
+                  }
+                ]
+              }
+            },
+            {
+              "name": "VariantH",
+              "shape": {
+                "Map": []
+              }
+            },
+            {
+              "name": "VariantI",
+              "shape": {
+                "Map": [
+                  {
+                    "name": "a",
+                    "schema": {
+                      "Primitive": "i32"
+                    }
+                  },
+                  {
+                    "name": "b",
+                    "schema": {
+                      "Named": {
+                        "name": "ImportedStruct",
+                        "module_path": "schemas::module",
+                        "params": []
+                      }
+                    }
+                  }
+                ]
+              }
+            }
+          ]
+        }
+      }
+    },
+    {
+      "name": "NestedModuleStruct",
+      "module_path": "schemas::module::nested_module",
+      "params": [],
+      "definition": {
+        "Struct": {
+          "Map": [
+            {
+              "name": "value",
+              "schema": {
+                "Named": {
+                  "name": "EnumAdjacentlyTagged",
+                  "module_path": "schemas::enumeration",
+                  "params": []
+                }
+              }
+            }
+          ]
+        }
+      }
+    },
+    {
+      "name": "EnumExternallyTagged",
+      "module_path": "schemas::enumeration",
+      "params": [],
+      "definition": {
+        "Enum": {
+          "representation": "External",
+          "variants": [
+            {
+              "name": "VariantA",
+              "shape": "Unit"
+            },
+            {
+              "name": "VariantB",
+              "shape": {
+                "Tuple": []
+              }
+            },
+            {
+              "name": "VariantC",
+              "shape": {
+                "Newtype": {
+                  "Primitive": "i32"
+                }
+              }
+            },
+            {
+              "name": "VariantD",
+              "shape": {
+                "Newtype": {
+                  "Primitive": "()"
+                }
+              }
+            },
+            {
+              "name": "VariantE",
+              "shape": {
+                "Newtype": {
+                  "Named": {
+                    "name": "ImportedStruct",
+                    "module_path": "schemas::module",
+                    "params": []
+                  }
+                }
+              }
+            },
+            {
+              "name": "VariantF",
+              "shape": {
+                "Newtype": {
+                  "Tuple": [
+                    {
+                      "Primitive": "i32"
+                    },
+                    {
+                      "Named": {
+                        "name": "ImportedStruct",
+                        "module_path": "schemas::module",
+                        "params": []
+                      }
+                    }
+                  ]
+                }
+              }
+            },
+            {
+              "name": "VariantG",
+              "shape": {
+                "Tuple": [
+                  {
+                    "Primitive": "i32"
+                  },
+                  {
+                    "Named": {
+                      "name": "ImportedStruct",
+                      "module_path": "schemas::module",
+                      "params": []
+                    }
+                  }
+                ]
+              }
+            },
+            {
+              "name": "VariantH",
+              "shape": {
+                "Map": []
+              }
+            },
+            {
+              "name": "VariantI",
+              "shape": {
+                "Map": [
+                  {
+                    "name": "a",
+                    "schema": {
+                      "Primitive": "i32"
+                    }
+                  },
+                  {
+                    "name": "b",
+                    "schema": {
+                      "Named": {
+                        "name": "ImportedStruct",
+                        "module_path": "schemas::module",
+                        "params": []
+                      }
+                    }
+                  }
+                ]
+              }
+            }
+          ]
+        }
+      }
+    },
+    {
+      "name": "TestEnums",
+      "module_path": "schemas::enumeration",
+      "params": [],
+      "definition": {
+        "Struct": {
+          "Map": [
+            {
+              "name": "external",
+              "schema": {
+                "Named": {
+                  "name": "EnumExternallyTagged",
+                  "module_path": "schemas::enumeration",
+                  "params": []
+                }
+              }
+            },
+            {
+              "name": "adjecent",
+              "schema": {
+                "Named": {
+                  "name": "EnumAdjacentlyTagged",
+                  "module_path": "schemas::enumeration",
+                  "params": []
+                }
+              }
+            }
+          ]
+        }
+      }
+    },
+    {
+      "name": "StructFromOtherCrate",
+      "module_path": "schemas_subcrate",
+      "params": [],
+      "definition": {
+        "Struct": {
+          "Map": [
+            {
+              "name": "value",
+              "schema": {
+                "Primitive": "i32"
+              }
+            }
+          ]
+        }
+      }
+    },
+    {
+      "name": "SequenceTypes",
+      "module_path": "schemas::sequence",
+      "params": [],
+      "definition": {
+        "Struct": {
+          "Newtype": {
+            "Tuple": [
+              {
+                "Sequence": {
+                  "Primitive": "String"
+                }
+              },
+              {
+                "Sequence": {
+                  "Primitive": "i32"
+                }
+              },
+              {
+                "Sequence": {
+                  "Sequence": {
+                    "Named": {
+                      "name": "ImportedStruct",
+                      "module_path": "schemas::module",
+                      "params": []
+                    }
+                  }
+                }
+              }
+            ]
+          }
+        }
+      }
+    },
+    {
+      "name": "UnitStruct",
+      "module_path": "schemas::structure",
+      "params": [],
+      "definition": {
+        "Struct": "Unit"
+      }
+    },
+    {
+      "name": "NewtypeStruct",
+      "module_path": "schemas::structure",
+      "params": [],
+      "definition": {
+        "Struct": {
+          "Newtype": {
+            "Tuple": [
+              {
+                "Sequence": {
+                  "Primitive": "String"
+                }
+              },
+              {
+                "Sequence": {
+                  "Primitive": "i32"
+                }
+              },
+              {
+                "Sequence": {
+                  "Sequence": {
+                    "Named": {
+                      "name": "ImportedStruct",
+                      "module_path": "schemas::module",
+                      "params": []
+                    }
+                  }
+                }
+              }
+            ]
+          }
+        }
+      }
+    },
+    {
+      "name": "TupleStruct",
+      "module_path": "schemas::structure",
+      "params": [],
+      "definition": {
+        "Struct": {
+          "Tuple": [
+            {
+              "Sequence": {
+                "Primitive": "String"
+              }
+            },
+            {
+              "Sequence": {
+                "Primitive": "i32"
+              }
+            },
+            {
+              "Sequence": {
+                "Sequence": {
+                  "Named": {
+                    "name": "ImportedStruct",
+                    "module_path": "schemas::module",
+                    "params": []
+                  }
+                }
+              }
+            }
+          ]
+        }
+      }
+    },
+    {
+      "name": "Result",
+      "module_path": "core::result",
+      "params": [
+        "T",
+        "E"
+      ],
+      "definition": {
+        "Enum": {
+          "representation": "External",
+          "variants": [
+            {
+              "name": "Ok",
+              "shape": {
+                "Newtype": {
+                  "Parameter": "T"
+                }
+              }
+            },
+            {
+              "name": "Err",
+              "shape": {
+                "Newtype": {
+                  "Parameter": "E"
+                }
+              }
+            }
+          ]
+        }
+      }
+    },
+    {
+      "name": "ResultsTest",
+      "module_path": "schemas::result",
+      "params": [
+        "T"
+      ],
+      "definition": {
+        "Struct": {
+          "Map": [
+            {
+              "name": "a",
+              "schema": {
+                "Named": {
+                  "name": "Result",
+                  "module_path": "core::result",
+                  "params": [
+                    {
+                      "Primitive": "String"
+                    },
+                    {
+                      "Primitive": "String"
+                    }
+                  ]
+                }
+              }
+            },
+            {
+              "name": "b",
+              "schema": {
+                "Named": {
+                  "name": "Result",
+                  "module_path": "core::result",
+                  "params": [
+                    {
+                      "Tuple": [
+                        {
+                          "Primitive": "String"
+                        },
+                        {
+                          "Primitive": "String"
+                        }
+                      ]
+                    },
+                    {
+                      "Tuple": [
+                        {
+                          "Primitive": "i32"
+                        },
+                        {
+                          "Primitive": "u32"
+                        }
+                      ]
+                    }
+                  ]
+                }
+              }
+            },
+            {
+              "name": "c",
+              "schema": {
+                "Named": {
+                  "name": "Result",
+                  "module_path": "core::result",
+                  "params": [
+                    {
+                      "Parameter": "T"
+                    },
+                    {
+                      "Primitive": "char"
+                    }
+                  ]
+                }
+              }
+            }
+          ]
+        }
+      }
+    },
+    {
+      "name": "Structure",
+      "module_path": "schemas::structure",
+      "params": [],
+      "definition": {
+        "Struct": {
+          "Map": [
+            {
+              "name": "name",
+              "schema": {
+                "Primitive": "String"
+              }
+            },
+            {
+              "name": "count",
+              "schema": {
+                "Primitive": "i32"
+              }
+            },
+            {
+              "name": "is_active",
+              "schema": {
+                "Primitive": "bool"
+              }
+            },
+            {
+              "name": "imported",
+              "schema": {
+                "Named": {
+                  "name": "ImportedStruct",
+                  "module_path": "schemas::module",
+                  "params": []
+                }
+              }
+            },
+            {
+              "name": "tuple",
+              "schema": {
+                "Tuple": [
+                  {
+                    "Primitive": "char"
+                  },
+                  {
+                    "Named": {
+                      "name": "ImportedStruct",
+                      "module_path": "schemas::module",
+                      "params": []
+                    }
+                  }
+                ]
+              }
+            },
+            {
+              "name": "nested_tuple",
+              "schema": {
+                "Tuple": [
+                  {
+                    "Primitive": "char"
+                  },
+                  {
+                    "Tuple": [
+                      {
+                        "Primitive": "i32"
+                      },
+                      {
+                        "Named": {
+                          "name": "NestedModuleStruct",
+                          "module_path": "schemas::module::nested_module",
+                          "params": []
+                        }
+                      }
+                    ]
+                  }
+                ]
+              }
+            },
+            {
+              "name": "enums",
+              "schema": {
+                "Named": {
+                  "name": "TestEnums",
+                  "module_path": "schemas::enumeration",
+                  "params": []
+                }
+              }
+            },
+            {
+              "name": "crate_dependency",
+              "schema": {
+                "Named": {
+                  "name": "StructFromOtherCrate",
+                  "module_path": "schemas_subcrate",
+                  "params": []
+                }
+              }
+            },
+            {
+              "name": "sequence",
+              "schema": {
+                "Named": {
+                  "name": "SequenceTypes",
+                  "module_path": "schemas::sequence",
+                  "params": []
+                }
+              }
+            },
+            {
+              "name": "structures",
+              "schema": {
+                "Tuple": [
+                  {
+                    "Named": {
+                      "name": "UnitStruct",
+                      "module_path": "schemas::structure",
+                      "params": []
+                    }
+                  },
+                  {
+                    "Named": {
+                      "name": "NewtypeStruct",
+                      "module_path": "schemas::structure",
+                      "params": []
+                    }
+                  },
+                  {
+                    "Named": {
+                      "name": "TupleStruct",
+                      "module_path": "schemas::structure",
+                      "params": []
+                    }
+                  }
+                ]
+              }
+            },
+            {
+              "name": "results",
+              "schema": {
+                "Named": {
+                  "name": "ResultsTest",
+                  "module_path": "schemas::result",
+                  "params": [
+                    {
+                      "Named": {
+                        "name": "ImportedStruct",
+                        "module_path": "schemas::module",
+                        "params": []
+                      }
+                    }
+                  ]
+                }
+              }
+            }
+          ]
+        }
+      }
+    }
+  ]
+};
@@ -1,12 +1,18 @@
-use rawr::{FieldDef, PrimitiveDef, Schema, SchemaDef, SchemaPtr, Shape, StructDef};
+use rawr::{FieldDef, Fingerprint, PrimitiveDef, Schema, SchemaDef, SchemaPtr, Shape, StructDef};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct StructFromOtherCrate {
     pub value: i32,
 }
 
 impl Schema for StructFromOtherCrate {
+    const FINGERPRINT: Fingerprint = Fingerprint::of_struct(
+        "StructFromOtherCrate",
+        &[],
+        Fingerprint::map(&[("value", i32::FINGERPRINT)]),
+    );
+
     fn schema() -> SchemaDef {
         SchemaDef::Struct(StructDef {
             name: "StructFromOtherCrate",
@@ -10,6 +10,15 @@ pub mod structure;
 pub fn export_to(path: &str) {
     typescript::Codegen::new()
         .export_type::<structure::Structure>()
+        .export_fingerprint::<(service::TestRequest, service::TestResponse)>(
+            "schemas::service",
+            "TEST_FINGERPRINT",
+        )
+        .export_constant(
+            "schemas::service",
+            "TEST_SERVICE",
+            &service::TestServer::description(),
+        )
         .export_to(path)
         .run()
 }
@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::enumeration::EnumAdjacentlyTagged;
 
-#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Schema)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Schema)]
 pub struct NestedModuleStruct {
     pub value: EnumAdjacentlyTagged,
 }
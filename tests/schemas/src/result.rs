@@ -1,7 +1,7 @@
 use rawr::Schema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Schema, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Schema, Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ResultsTest<T> {
     a: Result<String, String>,
     b: Result<(String, String), (i32, u32)>,
@@ -1,6 +1,11 @@
-use rawr::{AbstractClient, AbstractServer, ClientTransport, Result, ServerTransport};
+use rawr::futures::{Sink, Stream, StreamExt, future, stream};
+use rawr::{
+    AbstractClient, Accepted, Batch, Envelope, FieldDef, Fingerprint, Method, MethodDef,
+    MethodKind, Reconnect, Request, Responder, Result, Schema, SchemaPtr, Server, ServiceDef,
+    ServiceDescription,
+};
 use serde::{Deserialize, Serialize};
-use std::future::Future;
+use std::{fmt::Display, future::Future, time::Duration};
 
 use crate::{enumeration::EnumAdjacentlyTagged, structure::Structure};
 
@@ -10,26 +15,111 @@ pub trait TestService: Clone + 'static + Send + Sync {
     /// Service should increment `count` by `n`.
     async fn complex(&self, input: Structure, n: i32) -> Structure;
     async fn ping_enum(&self, arg: EnumAdjacentlyTagged) -> EnumAdjacentlyTagged;
+    /// Service should yield the numbers from 0 up to (excluding) `n`.
+    fn count_to(&self, n: u32) -> impl Stream<Item = u32>;
+    /// Service should return the sum of all uploaded `numbers`.
+    async fn sum(&self, numbers: impl Stream<Item = i32>) -> i32;
+    /// Notification: the client doesn't wait for the service to handle it.
+    async fn log_event(&self, event: String);
 }
 
 ///////////// GENERATED CODE /////////////
 
+/// Like [`TestService`], for services that aren't `Send` or `Sync`, e.g. because
+/// they keep their state in an `Rc<RefCell<_>>`. Their server has to run on a
+/// single thread, like a `tokio::task::LocalSet` or the browser.
+///
+/// Every [`TestService`] is a [`LocalTestService`] too.
+#[allow(async_fn_in_trait)]
+pub trait LocalTestService: Clone + 'static {
+    async fn say_hello(&self, arg: String) -> String;
+    /// Service should increment `count` by `n`.
+    async fn complex(&self, input: Structure, n: i32) -> Structure;
+    async fn ping_enum(&self, arg: EnumAdjacentlyTagged) -> EnumAdjacentlyTagged;
+    /// Service should yield the numbers from 0 up to (excluding) `n`.
+    fn count_to(&self, n: u32) -> impl Stream<Item = u32>;
+    /// Service should return the sum of all uploaded `numbers`.
+    async fn sum(&self, numbers: impl Stream<Item = i32>) -> i32;
+    /// Notification: the client doesn't wait for the service to handle it.
+    async fn log_event(&self, event: String);
+}
+
+impl<T: TestService> LocalTestService for T {
+    async fn say_hello(&self, arg: String) -> String {
+        TestService::say_hello(self, arg).await
+    }
+
+    async fn complex(&self, input: Structure, n: i32) -> Structure {
+        TestService::complex(self, input, n).await
+    }
+
+    async fn ping_enum(&self, arg: EnumAdjacentlyTagged) -> EnumAdjacentlyTagged {
+        TestService::ping_enum(self, arg).await
+    }
+
+    fn count_to(&self, n: u32) -> impl Stream<Item = u32> {
+        TestService::count_to(self, n)
+    }
+
+    async fn sum(&self, numbers: impl Stream<Item = i32>) -> i32 {
+        TestService::sum(self, numbers).await
+    }
+
+    async fn log_event(&self, event: String) {
+        TestService::log_event(self, event).await
+    }
+}
+
 #[allow(non_camel_case_types)]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Schema, Serialize, Deserialize)]
 #[serde(tag = "method", content = "payload")]
 pub enum TestRequest {
     complex((Structure, i32)),
     say_hello((String,)),
     ping_enum((EnumAdjacentlyTagged,)),
+    count_to((u32,)),
+    sum(()),
+    sum_chunk(i32),
+    log_event((String,)),
+    #[serde(rename = "rawr.describe")]
+    rawr_describe(()),
+}
+
+impl Method for TestRequest {
+    fn method(&self) -> &'static str {
+        match self {
+            TestRequest::complex(_) => "complex",
+            TestRequest::say_hello(_) => "say_hello",
+            TestRequest::ping_enum(_) => "ping_enum",
+            TestRequest::count_to(_) => "count_to",
+            TestRequest::sum(_) | TestRequest::sum_chunk(_) => "sum",
+            TestRequest::log_event(_) => "log_event",
+            TestRequest::rawr_describe(_) => rawr::DESCRIBE_METHOD,
+        }
+    }
+
+    fn is_idempotent(&self) -> bool {
+        matches!(
+            self,
+            TestRequest::complex(_)
+                | TestRequest::say_hello(_)
+                | TestRequest::ping_enum(_)
+                | TestRequest::rawr_describe(_)
+        )
+    }
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Schema, Serialize, Deserialize)]
 #[serde(tag = "method", content = "payload")]
 pub enum TestResponse {
     complex(Structure),
     say_hello(String),
     ping_enum(EnumAdjacentlyTagged),
+    count_to(u32),
+    sum(i32),
+    #[serde(rename = "rawr.describe")]
+    rawr_describe(ServiceDescription),
 }
 
 #[derive(Clone)]
@@ -40,11 +130,117 @@ pub struct TestClient {
 impl TestClient {
     /// Create a new client.
     pub fn new(
-        transport: ClientTransport<TestRequest, TestResponse>,
+        transport: (
+            impl Sink<Envelope<TestRequest>>,
+            impl Stream<Item = Envelope<Result<TestResponse>>>,
+        ),
     ) -> (Self, impl Future<Output = ()>) {
         let (inner, task) = AbstractClient::new(transport);
         (Self { inner }, task)
     }
+
+    /// Fingerprint of the service this client speaks, for a
+    /// [`Handshake`](rawr::Handshake).
+    pub const fn fingerprint() -> Fingerprint {
+        TestServer::fingerprint()
+    }
+
+    /// Create a new client that batches requests made within `window`, see
+    /// [`AbstractClient::with_batching`].
+    pub fn with_batching<F: Future<Output = ()>>(
+        transport: (
+            impl Sink<Envelope<TestRequest>>,
+            impl Stream<Item = Envelope<Result<TestResponse>>>,
+        ),
+        window: impl Fn() -> F,
+    ) -> (Self, impl Future<Output = ()>) {
+        let (inner, task) = AbstractClient::with_batching(transport, window);
+        (Self { inner }, task)
+    }
+
+    /// Create a client that dials its transport again whenever the connection is
+    /// lost, see [`AbstractClient::reconnecting`].
+    pub fn reconnecting<D, T, E, S, F, Tx, Rx>(
+        reconnect: Reconnect<D, S>,
+    ) -> (Self, impl Future<Output = ()>)
+    where
+        D: Fn() -> T,
+        T: Future<Output = std::result::Result<(Tx, Rx), E>>,
+        E: Display,
+        S: Fn(Duration) -> F,
+        F: Future<Output = ()>,
+        Tx: Sink<Envelope<TestRequest>>,
+        Rx: Stream<Item = Envelope<Result<TestResponse>>>,
+    {
+        let (inner, task) = AbstractClient::reconnecting(reconnect);
+        (Self { inner }, task)
+    }
+
+    /// Start a batch of requests, which are sent to the server all at once.
+    pub fn batch(&self) -> TestBatch<'_> {
+        TestBatch {
+            inner: self.inner.batch(),
+        }
+    }
+}
+
+pub struct TestBatch<'a> {
+    inner: Batch<'a, TestRequest, TestResponse>,
+}
+
+impl TestBatch<'_> {
+    pub fn say_hello(&mut self, arg: String) -> impl Future<Output = Result<String>> + use<> {
+        let res = self.inner.request(TestRequest::say_hello((arg,)));
+        async move {
+            match res.await {
+                Ok(TestResponse::say_hello(ret)) => Ok(ret),
+                Ok(_) => panic!("Unexpected response"),
+                Err(e) => Err(e),
+            }
+        }
+    }
+
+    pub fn complex(
+        &mut self,
+        arg0: Structure,
+        arg1: i32,
+    ) -> impl Future<Output = Result<Structure>> + use<> {
+        let res = self.inner.request(TestRequest::complex((arg0, arg1)));
+        async move {
+            match res.await {
+                Ok(TestResponse::complex(ret)) => Ok(ret),
+                Ok(_) => panic!("Unexpected response"),
+                Err(e) => Err(e),
+            }
+        }
+    }
+
+    pub fn ping_enum(
+        &mut self,
+        arg: EnumAdjacentlyTagged,
+    ) -> impl Future<Output = Result<EnumAdjacentlyTagged>> + use<> {
+        let res = self.inner.request(TestRequest::ping_enum((arg,)));
+        async move {
+            match res.await {
+                Ok(TestResponse::ping_enum(ret)) => Ok(ret),
+                Ok(_) => panic!("Unexpected response"),
+                Err(e) => Err(e),
+            }
+        }
+    }
+
+    /// Send all requests of the batch at once.
+    pub async fn send(self) -> Result<()> {
+        self.inner.send().await
+    }
+}
+
+impl From<AbstractClient<TestRequest, TestResponse>> for TestClient {
+    /// Wrap a client created by other means, such as an
+    /// [`AbstractPeer`](rawr::AbstractPeer).
+    fn from(inner: AbstractClient<TestRequest, TestResponse>) -> Self {
+        Self { inner }
+    }
 }
 
 impl TestClient {
@@ -74,11 +270,127 @@ impl TestClient {
             Err(e) => Err(e),
         }
     }
+
+    pub async fn count_to(&self, arg: u32) -> impl Stream<Item = Result<u32>> + use<> {
+        let req = TestRequest::count_to((arg,));
+        let items = self.inner.make_stream_request(req).await;
+        items.map(|res| match res {
+            Ok(TestResponse::count_to(item)) => Ok(item),
+            Ok(_) => panic!("Unexpected response"),
+            Err(e) => Err(e),
+        })
+    }
+
+    pub async fn sum(&self, arg: impl Stream<Item = i32>) -> Result<i32> {
+        let req = TestRequest::sum(());
+        let chunks = arg.map(TestRequest::sum_chunk);
+        match self.inner.make_upload_request(req, chunks).await {
+            Ok(TestResponse::sum(ret)) => Ok(ret),
+            Ok(_) => panic!("Unexpected response"),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn log_event(&self, arg: String) -> Result<()> {
+        self.inner.notify(TestRequest::log_event((arg,)))
+    }
+
+    /// Ask the server which service it offers, see [`rawr::DESCRIBE_METHOD`].
+    pub async fn describe(&self) -> Result<ServiceDescription> {
+        let req = TestRequest::rawr_describe(());
+        match self.inner.make_request(req).await {
+            Ok(TestResponse::rawr_describe(ret)) => Ok(ret),
+            Ok(_) => panic!("Unexpected response"),
+            Err(e) => Err(e),
+        }
+    }
 }
 
 pub struct TestServer;
 
 impl TestServer {
+    /// Definition of the service, which the server answers
+    /// [`rawr::DESCRIBE_METHOD`] with.
+    pub const SERVICE: ServiceDef = ServiceDef {
+        name: "TestService",
+        methods: &[
+            MethodDef {
+                name: "say_hello",
+                kind: MethodKind::Request,
+                params: &[FieldDef {
+                    name: "arg",
+                    schema: SchemaPtr(<String as Schema>::schema),
+                }],
+                returns: SchemaPtr(<String as Schema>::schema),
+            },
+            MethodDef {
+                name: "complex",
+                kind: MethodKind::Request,
+                params: &[
+                    FieldDef {
+                        name: "input",
+                        schema: SchemaPtr(<Structure as Schema>::schema),
+                    },
+                    FieldDef {
+                        name: "n",
+                        schema: SchemaPtr(<i32 as Schema>::schema),
+                    },
+                ],
+                returns: SchemaPtr(<Structure as Schema>::schema),
+            },
+            MethodDef {
+                name: "ping_enum",
+                kind: MethodKind::Request,
+                params: &[FieldDef {
+                    name: "arg",
+                    schema: SchemaPtr(<EnumAdjacentlyTagged as Schema>::schema),
+                }],
+                returns: SchemaPtr(<EnumAdjacentlyTagged as Schema>::schema),
+            },
+            MethodDef {
+                name: "count_to",
+                kind: MethodKind::Stream,
+                params: &[FieldDef {
+                    name: "n",
+                    schema: SchemaPtr(<u32 as Schema>::schema),
+                }],
+                returns: SchemaPtr(<u32 as Schema>::schema),
+            },
+            MethodDef {
+                name: "sum",
+                kind: MethodKind::Upload,
+                params: &[FieldDef {
+                    name: "numbers",
+                    schema: SchemaPtr(<i32 as Schema>::schema),
+                }],
+                returns: SchemaPtr(<i32 as Schema>::schema),
+            },
+            MethodDef {
+                name: "log_event",
+                kind: MethodKind::Notification,
+                params: &[FieldDef {
+                    name: "event",
+                    schema: SchemaPtr(<String as Schema>::schema),
+                }],
+                returns: SchemaPtr(<() as Schema>::schema),
+            },
+        ],
+        schema: SchemaPtr(<(TestRequest, TestResponse) as Schema>::schema),
+        fingerprint: <(TestRequest, TestResponse)>::FINGERPRINT,
+    };
+
+    /// Fingerprint of the service this server speaks, for a
+    /// [`Handshake`](rawr::Handshake). The TypeScript bindings export it as
+    /// `TEST_FINGERPRINT`.
+    pub const fn fingerprint() -> Fingerprint {
+        Self::SERVICE.fingerprint
+    }
+
+    /// Description of the service, see [`rawr::DESCRIBE_METHOD`].
+    pub fn description() -> ServiceDescription {
+        ServiceDescription::of(&Self::SERVICE)
+    }
+
     /// Create a new server. Returns a future that must be spawned on a runtime for
     /// the server to start processing requests.
     ///
@@ -98,24 +410,128 @@ impl TestServer {
     /// println!("{}", response);
     /// ```
     pub fn new(
-        server_transport: ServerTransport<TestRequest, TestResponse>,
+        server_transport: (
+            impl Stream<Item = Envelope<TestRequest>>,
+            impl Sink<Envelope<Result<TestResponse>>>,
+        ),
         service_handler: impl TestService,
     ) -> impl Future<Output = ()> {
-        let handle_request = async move |req: TestRequest| match req {
+        Self::serve_connections(
+            stream::once(future::ready(server_transport)),
+            service_handler,
+        )
+    }
+
+    /// Serve many clients at once. Each transport yielded by `transports` is a
+    /// separate connection, all of them sharing the same `service_handler`.
+    pub fn serve_connections<Rx, Tx>(
+        transports: impl Stream<Item = (Rx, Tx)>,
+        service_handler: impl TestService,
+    ) -> impl Future<Output = ()>
+    where
+        Rx: Stream<Item = Envelope<TestRequest>>,
+        Tx: Sink<Envelope<Result<TestResponse>>>,
+    {
+        let handle_request = async move |req, responder| {
+            Self::handle_request(&service_handler, req, responder).await
+        };
+        Server::new(handle_request).serve(transports)
+    }
+
+    /// Like [`new`](Self::new), for a service that isn't `Send`. The returned
+    /// future isn't `Send` either, so it must be spawned on a single-threaded
+    /// runtime, e.g. with `tokio::task::spawn_local`.
+    pub fn new_local(
+        server_transport: (
+            impl Stream<Item = Envelope<TestRequest>>,
+            impl Sink<Envelope<Result<TestResponse>>>,
+        ),
+        service_handler: impl LocalTestService,
+    ) -> impl Future<Output = ()> {
+        Self::serve_local_connections(
+            stream::once(future::ready(server_transport)),
+            service_handler,
+        )
+    }
+
+    /// Like [`serve_connections`](Self::serve_connections), for a service that
+    /// isn't `Send`.
+    pub fn serve_local_connections<Rx, Tx>(
+        transports: impl Stream<Item = (Rx, Tx)>,
+        service_handler: impl LocalTestService,
+    ) -> impl Future<Output = ()>
+    where
+        Rx: Stream<Item = Envelope<TestRequest>>,
+        Tx: Sink<Envelope<Result<TestResponse>>>,
+    {
+        let handle_request = async move |req, responder| {
+            Self::handle_request(&service_handler, req, responder).await
+        };
+        Server::new(handle_request).serve(transports)
+    }
+
+    /// Like [`serve_connections`](Self::serve_connections), for connections that
+    /// come with what is known about the client, e.g. its address or the user
+    /// it authenticated as. `service_handler` finds it in
+    /// [`Context::current`](rawr::Context::current).
+    pub fn serve_accepted<Rx, Tx>(
+        accepted: impl Stream<Item = Accepted<Rx, Tx>>,
+        service_handler: impl TestService,
+    ) -> impl Future<Output = ()>
+    where
+        Rx: Stream<Item = Envelope<TestRequest>>,
+        Tx: Sink<Envelope<Result<TestResponse>>>,
+    {
+        let handle_request = async move |req, responder| {
+            Self::handle_request(&service_handler, req, responder).await
+        };
+        Server::new(handle_request).serve_accepted(accepted)
+    }
+
+    /// Dispatch a single request to `service_handler`. Useful for serving this
+    /// service with [`AbstractPeer`](rawr::AbstractPeer), while calling another
+    /// one over the same transport.
+    pub async fn handle_request(
+        service_handler: &impl LocalTestService,
+        req: Request<TestRequest>,
+        responder: Responder<TestResponse>,
+    ) {
+        match req.data {
             TestRequest::say_hello((arg0,)) => {
                 let res = service_handler.say_hello(arg0).await;
-                Ok(TestResponse::say_hello(res))
+                responder.respond(Ok(TestResponse::say_hello(res))).await
             }
             TestRequest::complex((arg0, arg1)) => {
                 let res = service_handler.complex(arg0, arg1).await;
-                Ok(TestResponse::complex(res))
+                responder.respond(Ok(TestResponse::complex(res))).await
             }
             TestRequest::ping_enum((arg0,)) => {
                 let res = service_handler.ping_enum(arg0).await;
-                Ok(TestResponse::ping_enum(res))
+                responder.respond(Ok(TestResponse::ping_enum(res))).await
             }
-        };
-
-        AbstractServer::new(server_transport, handle_request)
+            TestRequest::count_to((arg0,)) => {
+                let items = service_handler.count_to(arg0);
+                let items = items.map(|item| Ok(TestResponse::count_to(item)));
+                responder.respond_stream(items).await
+            }
+            TestRequest::sum(()) => {
+                let numbers = req.uploads.filter_map(async |chunk| match chunk {
+                    TestRequest::sum_chunk(number) => Some(number),
+                    _ => None,
+                });
+                let res = service_handler.sum(numbers).await;
+                responder.respond(Ok(TestResponse::sum(res))).await
+            }
+            TestRequest::log_event((arg0,)) => service_handler.log_event(arg0).await,
+            TestRequest::rawr_describe(()) => {
+                let res = Self::description();
+                responder
+                    .respond(Ok(TestResponse::rawr_describe(res)))
+                    .await
+            }
+            // Chunks are only valid within an upload. Dropping the responder
+            // answers a stray one with `Cancelled`.
+            TestRequest::sum_chunk(_) => {}
+        }
     }
 }
@@ -9,7 +9,7 @@ use crate::{
     sequence::SequenceTypes,
 };
 
-#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Schema)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Schema)]
 pub struct Structure {
     pub name: String,
     pub count: i32,
@@ -24,11 +24,11 @@ pub struct Structure {
     pub results: ResultsTest<ImportedStruct>,
 }
 
-#[derive(Debug, Default, Schema, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Schema, Serialize, Deserialize, PartialEq)]
 pub struct UnitStruct;
 
-#[derive(Debug, Default, Schema, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Schema, Serialize, Deserialize, PartialEq)]
 pub struct NewtypeStruct((Vec<String>, [i32; 3], Vec<Vec<ImportedStruct>>));
 
-#[derive(Debug, Default, Schema, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Schema, Serialize, Deserialize, PartialEq)]
 pub struct TupleStruct(Vec<String>, [i32; 3], Vec<Vec<ImportedStruct>>);
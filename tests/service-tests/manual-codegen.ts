@@ -2,15 +2,19 @@ import { RpcClient } from "rawr-json";
 import type { HandleRequest, Result } from "rawr-json";
 import type { Structure } from "./typescript-bindings/schemas/structure";
 import type { EnumAdjacentlyTagged } from "./typescript-bindings/schemas/enumeration";
+import { TEST_SERVICE } from "./typescript-bindings/schemas/service";
 
 export type TestRequest =
   | { method: "say_hello"; payload: [string] }
   | { method: "complex"; payload: [Structure, number] }
-  | { method: "ping_enum"; payload: [EnumAdjacentlyTagged] };
+  | { method: "ping_enum"; payload: [EnumAdjacentlyTagged] }
+  | { method: "log_event"; payload: [string] }
+  | { method: "rawr.describe"; payload: null };
 export type TestResponse =
   | { method: "say_hello"; payload: string }
   | { method: "complex"; payload: Structure }
-  | { method: "ping_enum"; payload: EnumAdjacentlyTagged };
+  | { method: "ping_enum"; payload: EnumAdjacentlyTagged }
+  | { method: "rawr.describe"; payload: typeof TEST_SERVICE };
 
 export function TestClient(rpcClient: RpcClient<TestRequest, TestResponse>) {
   return {
@@ -28,6 +32,10 @@ export function TestClient(rpcClient: RpcClient<TestRequest, TestResponse>) {
       const result = await rpcClient.request("ping_enum", [arg]);
       return result.payload as EnumAdjacentlyTagged;
     },
+    /** Notification: doesn't wait for the service to handle it. */
+    log_event: function (event: string): void {
+      rpcClient.notify("log_event", [event]);
+    },
   };
 }
 
@@ -40,9 +48,10 @@ export type TestService = {
   ) => EnumAdjacentlyTagged | Promise<EnumAdjacentlyTagged>;
 };
 
-export function TestServer(
-  service: TestService
-): HandleRequest<TestRequest, Result<TestResponse>> {
+/** Handler for requests to `TestService`, e.g. for `RpcPeer`. */
+export type TestHandler = HandleRequest<TestRequest, Result<TestResponse>>;
+
+export function TestServer(service: TestService): TestHandler {
   return async (request) => {
     try {
       switch (request.data.method) {
@@ -79,6 +88,17 @@ export function TestServer(
               },
             },
           };
+        case "log_event":
+          // Notifications aren't requests, so they get no response.
+          return {
+            id: request.id,
+            data: { Err: "log_event is a notification" },
+          };
+        case "rawr.describe":
+          return {
+            id: request.id,
+            data: { Ok: { method: "rawr.describe", payload: TEST_SERVICE } },
+          };
       }
     } catch (error) {
       // TODO: I don't think catching all errors is a good idea. I think we should
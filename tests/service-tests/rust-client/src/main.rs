@@ -1,57 +1,30 @@
-use futures::{SinkExt, StreamExt, future, stream};
+use futures::{StreamExt, stream};
+use rawr::{DynamicClient, Handshake, RequestError};
 use schemas::{
-    enumeration::EnumAdjacentlyTagged,
-    module::ImportedStruct,
-    service::{TestClient, TestResponse},
+    enumeration::EnumAdjacentlyTagged, module::ImportedStruct, service::TestClient,
     structure::Structure,
 };
-use tokio_tungstenite::{
-    connect_async,
-    tungstenite::{Error, error::ProtocolError, protocol::Message},
-};
+use serde_json::json;
+use tokio::time::{self, Duration};
 
 #[tokio::main]
 async fn main() {
     let addr = std::env::var("SERVER_ADDR").expect("SERVER_ADDR not set");
 
-    let (client_transport, server_transport) = rawr::transport();
+    let transport = rawr::transport::websocket::connect(&format!("ws://{}", addr))
+        .await
+        .unwrap();
+
+    // Refuse to talk to a server that speaks another version of the service.
+    let handshake = Handshake::new(TestClient::fingerprint()).reject_mismatch();
+    let (transport, handshake_task) = handshake.client(transport);
 
     // Create client.
-    let (client, client_task) = TestClient::new(client_transport);
+    let (client, client_task) = TestClient::new(transport);
 
-    // Spawn the client task.
+    // Spawn the client and handshake tasks.
     tokio::spawn(client_task);
-
-    // Handle communication with the server.
-    tokio::spawn(async move {
-        let ws = connect_async(&format!("ws://{}", addr)).await.unwrap().0;
-
-        let (mut socket_tx, mut socket_rx) = ws.split();
-        let (mut client_rx, client_tx) = server_transport;
-
-        let handle_incoming = async {
-            while let Some(msg) = socket_rx.next().await {
-                let msg = match msg {
-                    Ok(Message::Close(_))
-                    | Err(Error::Protocol(ProtocolError::ResetWithoutClosingHandshake)) => break,
-                    Ok(msg) => msg,
-                    Err(e) => panic!("{:?}", e),
-                };
-                let msg: rawr::Packet<rawr::Result<TestResponse>> =
-                    serde_json::from_str(&msg.to_string()).unwrap();
-                client_tx.send(msg);
-            }
-        };
-
-        let handle_outgoing = async {
-            while let Some(req) = client_rx.recv().await {
-                let req = Message::text(serde_json::to_string(&req).unwrap());
-                socket_tx.send(req).await.unwrap();
-            }
-        };
-
-        future::join(handle_incoming, handle_outgoing).await;
-    });
+    tokio::spawn(handshake_task);
 
     // Make 10 concurrent requests to the server.
     let client = &client;
@@ -121,4 +94,23 @@ async fn main() {
     };
     let res = client.ping_enum(en.clone()).await.unwrap();
     assert_eq!(res, en);
+
+    //// Calling methods by name
+
+    let transport = rawr::transport::websocket::connect(&format!("ws://{}", addr))
+        .await
+        .unwrap();
+    let (client, client_task) = DynamicClient::new(transport);
+    let client = client.timeout(|| time::sleep(Duration::from_secs(5)));
+    tokio::spawn(client_task);
+
+    // ASSERT: The server answers `rawr.describe` rather than leaving it pending.
+    let description = client.describe().await.unwrap();
+    assert_eq!(description.fingerprint, TestClient::fingerprint());
+    let client = client.validate_with(description);
+
+    let res = client.call("say_hello", json!(["World"])).await.unwrap();
+    assert_eq!(res, json!("Hello, World!"));
+    let res = client.call("say_hello", json!([42])).await;
+    assert!(matches!(res, Err(RequestError::Invalid(_))));
 }
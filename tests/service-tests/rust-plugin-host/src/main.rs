@@ -0,0 +1,53 @@
+//! Spawns `rust_plugin` as a child process and calls `TestService` on it over
+//! its stdin/stdout.
+
+use std::process::Stdio;
+
+use rawr::{
+    codec::Json,
+    futures::{StreamExt, stream},
+    transport::stdio::Framing,
+};
+use schemas::{service::TestClient, structure::Structure};
+use tokio::process::Command;
+
+#[tokio::main]
+async fn main() {
+    // The plugin is built into the same directory as this binary.
+    let plugin_path = std::env::current_exe()
+        .unwrap()
+        .with_file_name("rust_plugin");
+
+    for (framing, name) in [
+        (Framing::Lines, "lines"),
+        (Framing::ContentLength, "content-length"),
+    ] {
+        let mut plugin = Command::new(&plugin_path)
+            .env("PLUGIN_FRAMING", name)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .expect("Failed to spawn plugin, run `cargo build --bin rust_plugin` first");
+
+        let transport = rawr::transport::stdio::client(&mut plugin, Json, framing);
+        let (client, client_task) = TestClient::new(transport);
+        tokio::spawn(client_task);
+
+        let response = client.say_hello("Plugin".to_string()).await.unwrap();
+        assert_eq!(response, "Hello, Plugin!");
+
+        let res = client.complex(Structure::default(), 42).await.unwrap();
+        assert_eq!(res.count, 42);
+
+        let items: Vec<_> = client.count_to(3).await.collect().await;
+        assert!(matches!(items[..], [Ok(0), Ok(1), Ok(2)]));
+
+        let sum = client.sum(stream::iter([1, 2, 3])).await.unwrap();
+        assert_eq!(sum, 6);
+
+        client.log_event("plugin notified".to_string()).unwrap();
+
+        println!("{}: ok", name);
+    }
+}
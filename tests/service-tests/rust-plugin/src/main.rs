@@ -0,0 +1,55 @@
+//! Serves `TestService` over stdin/stdout. Spawned by `rust_plugin_host`.
+
+use rawr::{
+    codec::Json,
+    futures::{Stream, StreamExt, stream},
+    transport::stdio::Framing,
+};
+use schemas::enumeration::EnumAdjacentlyTagged;
+use schemas::service::{TestServer, TestService};
+use schemas::structure::Structure;
+
+#[derive(Clone)]
+struct ServiceImpl {}
+
+impl TestService for ServiceImpl {
+    async fn say_hello(&self, arg: String) -> String {
+        format!("Hello, {}!", arg)
+    }
+
+    async fn complex(&self, mut input: Structure, n: i32) -> Structure {
+        input.count += n;
+        input
+    }
+
+    async fn ping_enum(&self, arg: EnumAdjacentlyTagged) -> EnumAdjacentlyTagged {
+        arg
+    }
+
+    fn count_to(&self, n: u32) -> impl Stream<Item = u32> {
+        stream::iter(0..n)
+    }
+
+    async fn sum(&self, numbers: impl Stream<Item = i32>) -> i32 {
+        numbers.fold(0, async |sum, n| sum + n).await
+    }
+
+    async fn log_event(&self, event: String) {
+        eprintln!("Event: {}", event);
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    // NOTE: env_logger writes to stderr, stdout is reserved for the transport.
+    env_logger::init();
+
+    let framing = match std::env::var("PLUGIN_FRAMING").as_deref() {
+        Ok("content-length") => Framing::ContentLength,
+        _ => Framing::Lines,
+    };
+
+    // Serve until the host closes our stdin.
+    let transport = rawr::transport::stdio::server(Json, framing);
+    TestServer::new(transport, ServiceImpl {}).await;
+}
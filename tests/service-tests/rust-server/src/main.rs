@@ -1,12 +1,9 @@
-use futures::{SinkExt, StreamExt, future};
+use futures::{Stream, StreamExt, stream};
+use rawr::{Accepted, Context, Handshake, Server, TokioSpawner};
 use schemas::enumeration::EnumAdjacentlyTagged;
-use schemas::service::{TestRequest, TestServer, TestService};
+use schemas::service::{TestServer, TestService};
 use schemas::structure::Structure;
 use tokio::net::TcpListener;
-use tokio_tungstenite::accept_async;
-use tokio_tungstenite::tungstenite::Error;
-use tokio_tungstenite::tungstenite::error::ProtocolError;
-use tokio_tungstenite::tungstenite::protocol::Message;
 
 #[derive(Clone)]
 struct ServiceImpl {}
@@ -24,6 +21,19 @@ impl TestService for ServiceImpl {
     async fn ping_enum(&self, arg: EnumAdjacentlyTagged) -> EnumAdjacentlyTagged {
         arg
     }
+
+    fn count_to(&self, n: u32) -> impl Stream<Item = u32> {
+        stream::iter(0..n)
+    }
+
+    async fn sum(&self, numbers: impl Stream<Item = i32>) -> i32 {
+        numbers.fold(0, async |sum, n| sum + n).await
+    }
+
+    async fn log_event(&self, event: String) {
+        let peer_addr = Context::current().and_then(|ctx| ctx.peer_addr());
+        log::info!("Event from {:?}: {}", peer_addr, event);
+    }
 }
 
 #[tokio::main]
@@ -34,39 +44,39 @@ async fn main() {
     let addr = std::env::var("SERVER_ADDR").expect("SERVER_ADDR not set");
     let listener = TcpListener::bind(addr).await.expect("Failed to bind");
 
-    let (mut client_transport, server_transport) = rawr::transport();
-    let server_task = TestServer::new(server_transport, ServiceImpl {});
-    tokio::spawn(server_task);
-
-    while let Ok((stream, _)) = listener.accept().await {
-        let ws_stream = accept_async(stream).await.expect("Failed to accept");
-        let (mut write, mut read) = ws_stream.split();
-        // TODO: You should probably multiplex here, handle multiple clients concurrently.
-        let (server_tx, server_rx) = &mut client_transport;
-
-        let handle_incoming = Box::pin(async {
-            while let Some(msg) = read.next().await {
-                let msg = match msg {
-                    Ok(Message::Close(_))
-                    | Err(Error::Protocol(ProtocolError::ResetWithoutClosingHandshake)) => break,
-                    Ok(msg) => msg,
-                    Err(e) => panic!("{:?}", e),
-                };
-                log::debug!("Received message: {}", msg);
-                let msg: rawr::Packet<TestRequest> =
-                    serde_json::from_str(&msg.to_string()).unwrap();
-                server_tx.send(msg);
-            }
-        });
+    // Perform the WebSocket handshake of up to 16 clients at once, so that a slow
+    // client can't hold up the others.
+    let accepted = stream::unfold(listener, async |listener| {
+        let (stream, addr) = listener.accept().await.ok()?;
+        Some(((stream, addr), listener))
+    })
+    .map(async |(stream, addr)| {
+        let transport = rawr::transport::websocket::accept(stream).await;
+        transport.map(|transport| {
+            // Clients that speak another version of the service are only warned
+            // about, so that clients without a handshake keep working too.
+            let handshake = Handshake::new(TestServer::fingerprint());
+            let (transport, handshake_task) = handshake.server(transport);
+            tokio::spawn(handshake_task);
+            Accepted::new(transport).peer_addr(addr)
+        })
+    })
+    .buffer_unordered(16)
+    .filter_map(async |accepted| match accepted {
+        Ok(accepted) => Some(accepted),
+        Err(e) => {
+            log::error!("Failed to accept: {}", e);
+            None
+        }
+    });
 
-        let handle_outgoing = Box::pin(async {
-            while let Some(res) = server_rx.recv().await {
-                let res = Message::text(serde_json::to_string(&res).unwrap());
-                log::debug!("Sending message: {}", res);
-                write.send(res).await.unwrap();
-            }
-        });
-
-        future::select(handle_incoming, handle_outgoing).await;
-    }
+    // Serve every connection and handle every request as a task of its own, on
+    // all threads of the runtime.
+    let service = ServiceImpl {};
+    let handle_request = move |req, responder| {
+        let service = service.clone();
+        async move { TestServer::handle_request(&service, req, responder).await }
+    };
+    let server = Server::new(handle_request).spawn_with(TokioSpawner);
+    server.serve_accepted(accepted).await;
 }
@@ -1,12 +1,31 @@
 //! TODO: This should probably be in /examples.
 
-use futures::stream::{self, StreamExt};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use futures::stream::{self, Stream, StreamExt};
+use rawr::{
+    AbstractClient, AbstractPeer, ConcurrencyLimit, Envelope, Handler, Interceptor, Method,
+    Middleware, Request, Responder, Server,
+    codec::{Cbor, Codec, Json, MessagePack, Postcard},
+};
 use schemas::{
-    enumeration::EnumAdjacentlyTagged,
-    service::{TestClient, TestServer, TestService},
+    enumeration::{EnumAdjacentlyTagged, TestEnums},
+    module::nested_module::NestedModuleStruct,
+    service::{LocalTestService, TestClient, TestServer, TestService},
     structure::Structure,
 };
-use tokio::time::{self, Duration};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    task::{self, LocalSet},
+    time::{self, Duration},
+};
 
 #[derive(Clone)]
 struct ServiceImpl {}
@@ -28,10 +47,91 @@ impl TestService for ServiceImpl {
     async fn ping_enum(&self, arg: EnumAdjacentlyTagged) -> EnumAdjacentlyTagged {
         arg
     }
+
+    fn count_to(&self, n: u32) -> impl Stream<Item = u32> {
+        stream::iter(0..n)
+    }
+
+    async fn sum(&self, numbers: impl Stream<Item = i32>) -> i32 {
+        numbers.fold(0, async |sum, n| sum + n).await
+    }
+
+    async fn log_event(&self, event: String) {
+        println!("Event: {}", event);
+    }
+}
+
+/// Keeps its state in an `Rc<RefCell<_>>`, so it isn't `Send`.
+#[derive(Clone, Default)]
+struct LocalServiceImpl {
+    state: Rc<RefCell<LocalState>>,
+}
+
+#[derive(Default)]
+struct LocalState {
+    hellos: usize,
+}
+
+impl LocalTestService for LocalServiceImpl {
+    async fn say_hello(&self, arg: String) -> String {
+        self.state.borrow_mut().hellos += 1;
+        format!("Hello, {}!", arg)
+    }
+
+    async fn complex(&self, mut input: Structure, n: i32) -> Structure {
+        input.count += n;
+        input
+    }
+
+    async fn ping_enum(&self, arg: EnumAdjacentlyTagged) -> EnumAdjacentlyTagged {
+        arg
+    }
+
+    fn count_to(&self, n: u32) -> impl Stream<Item = u32> {
+        stream::iter(0..n)
+    }
+
+    async fn sum(&self, numbers: impl Stream<Item = i32>) -> i32 {
+        numbers.fold(0, async |sum, n| sum + n).await
+    }
+
+    async fn log_event(&self, event: String) {
+        println!("Local event: {}", event);
+    }
+}
+
+/// Prints every request the server has handled.
+#[derive(Clone)]
+struct Logging;
+
+impl<Req: Method, Res> Middleware<Req, Res> for Logging {
+    async fn call(
+        &self,
+        req: Request<Req>,
+        responder: Responder<Res>,
+        next: &impl Handler<Req, Res>,
+    ) {
+        let method = req.data.method();
+        next.handle(req, responder).await;
+        println!("Handled {}", method);
+    }
+}
+
+/// Counts the requests a client has sent.
+struct CountRequests(Arc<AtomicUsize>);
+
+impl<Req, Res> Interceptor<Req, Res> for CountRequests {
+    fn request(&self, envelope: &mut Envelope<Req>) {
+        if let Envelope::Packet(_) = envelope {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() {
+    //// In-memory
+
     let (client_transport, server_transport) = rawr::transport();
 
     // Create server and client.
@@ -42,8 +142,123 @@ async fn main() {
     tokio::spawn(client_task);
     tokio::spawn(server_task);
 
+    test_service(&client).await;
+
+    //// Batching
+
+    let (client_transport, server_transport) = rawr::transport();
+    let window = || time::sleep(Duration::from_millis(5));
+    let (client, client_task) = TestClient::with_batching(client_transport, window);
+    tokio::spawn(client_task);
+    tokio::spawn(TestServer::new(server_transport, ServiceImpl {}));
+
+    test_service(&client).await;
+
+    let mut batch = client.batch();
+    let hello = batch.say_hello("Batch".to_string());
+    let variant = batch.ping_enum(EnumAdjacentlyTagged::VariantC(3));
+    batch.send().await.unwrap();
+    let (hello, variant) = futures::join!(hello, variant);
+    assert_eq!(hello.unwrap(), "Hello, Batch!");
+    assert_eq!(variant.unwrap(), EnumAdjacentlyTagged::VariantC(3));
+
+    //// Middleware
+
+    let (client_transport, server_transport) = rawr::transport();
+    let (client, client_task) = AbstractClient::new(client_transport);
+    let requests = Arc::new(AtomicUsize::new(0));
+    client.intercept(CountRequests(requests.clone()));
+    let client = TestClient::from(client);
+    tokio::spawn(client_task);
+
+    let handle_request =
+        async |req, responder| TestServer::handle_request(&ServiceImpl {}, req, responder).await;
+    let limit = ConcurrencyLimit::new(16).method("count_to", 1);
+    let server = Server::new(handle_request).layer(Logging).layer(limit);
+    tokio::spawn(server.serve(stream::once(async { server_transport })));
+
+    let response = client.say_hello("Middleware".to_string()).await.unwrap();
+    assert_eq!(response, "Hello, Middleware!");
+    assert_eq!(requests.load(Ordering::Relaxed), 1);
+
+    //// Local
+
+    LocalSet::new().run_until(test_local()).await;
+
+    //// Over a TCP socket
+
+    test_over_tcp(Json).await;
+    test_over_tcp(MessagePack).await;
+    test_over_tcp(Cbor).await;
+    test_over_tcp(Postcard).await;
+
+    //// Peers
+
+    test_peers().await;
+}
+
+async fn test_over_tcp(codec: impl Codec + Send + 'static) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server_codec = codec.clone();
+    let server_task = async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let server_transport = rawr::transport::stream::server(socket, server_codec);
+        TestServer::new(server_transport, ServiceImpl {}).await;
+    };
+    tokio::spawn(server_task);
+
+    let socket = TcpStream::connect(addr).await.unwrap();
+    let client_transport = rawr::transport::stream::client(socket, codec);
+    let (client, client_task) = TestClient::new(client_transport);
+    tokio::spawn(client_task);
+
+    test_service(&client).await;
+}
+
+/// Serves a `!Send` service on the current thread.
+async fn test_local() {
+    let (client_transport, server_transport) = rawr::transport();
+    let service = LocalServiceImpl::default();
+
+    let (client, client_task) = TestClient::new(client_transport);
+    let server_task = TestServer::new_local(server_transport, service.clone());
+    tokio::spawn(client_task);
+    task::spawn_local(server_task);
+
+    test_service(&client).await;
+    assert_eq!(service.state.borrow().hellos, 11);
+}
+
+/// Both ends serve `TestService` and call each other over a single socket.
+async fn test_peers() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let a_socket = TcpStream::connect(addr).await.unwrap();
+    let (b_socket, _) = listener.accept().await.unwrap();
+
+    let handle_request =
+        async |req, responder| TestServer::handle_request(&ServiceImpl {}, req, responder).await;
+    let (a, a_task) = AbstractPeer::serve(
+        rawr::transport::stream::peer(a_socket, Json),
+        handle_request,
+    );
+    let (b, b_task) = AbstractPeer::serve(
+        rawr::transport::stream::peer(b_socket, Json),
+        handle_request,
+    );
+    tokio::spawn(a_task);
+    tokio::spawn(b_task);
+
+    let (a, b) = (TestClient::from(a), TestClient::from(b));
+    test_service(&a).await;
+    let response = b.say_hello("Peer".to_string()).await.unwrap();
+    assert_eq!(response, "Hello, Peer!");
+}
+
+async fn test_service(client: &TestClient) {
     // Make 10 concurrent requests to the server.
-    let client = &client;
     let make_request = async move |i| {
         let response = client.say_hello(format!("World {}", i + 1)).await.unwrap();
         println!("{}: {}", i + 1, response);
@@ -56,5 +271,64 @@ async fn main() {
         panic!("test took more than 1 second to complete");
     }
 
-    client.complex(Structure::default(), 42).await.unwrap();
+    // Postcard can't carry unit variants of adjacently tagged enums, like the
+    // default ones.
+    let value = EnumAdjacentlyTagged::VariantC(1);
+    let structure = Structure {
+        nested_tuple: (
+            'a',
+            (
+                1,
+                NestedModuleStruct {
+                    value: value.clone(),
+                },
+            ),
+        ),
+        enums: TestEnums {
+            adjecent: value,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let response = client.complex(structure.clone(), 42).await.unwrap();
+    assert_eq!(
+        response,
+        Structure {
+            count: 42,
+            ..structure
+        }
+    );
+
+    let variant = EnumAdjacentlyTagged::VariantC(7);
+    assert_eq!(client.ping_enum(variant.clone()).await.unwrap(), variant);
+
+    //// Streaming responses
+
+    let items: Vec<_> = client.count_to(5).await.map(Result::unwrap).collect().await;
+    assert_eq!(items, [0, 1, 2, 3, 4]);
+
+    // Dropping the stream early cancels the rest of it.
+    let items: Vec<_> = client.count_to(u32::MAX).await.take(3).collect().await;
+    assert_eq!(items.len(), 3);
+    client.say_hello("again".to_string()).await.unwrap();
+
+    //// Notifications
+
+    client.log_event("notified".to_string()).unwrap();
+
+    //// Upload streams
+
+    let sum = client.sum(stream::iter(1..=100)).await.unwrap();
+    assert_eq!(sum, 5050);
+    let sum = client.sum(stream::empty()).await.unwrap();
+    assert_eq!(sum, 0);
+
+    //// Introspection
+
+    let description = client.describe().await.unwrap();
+    assert_eq!(description, TestServer::description());
+    assert_eq!(description.fingerprint, TestClient::fingerprint());
+    let complex = description.method("complex").unwrap();
+    let structure = description.definition(&complex.returns).unwrap();
+    assert_eq!(structure.name, "Structure");
 }
@@ -9,6 +9,7 @@ import { RpcClient } from "rawr-json";
 import type { Packet, Result } from "rawr-json";
 import type { Structure } from "../../typescript-bindings/schemas/structure";
 import type { EnumAdjacentlyTagged } from "../../typescript-bindings/schemas/enumeration";
+import { TEST_FINGERPRINT } from "../../typescript-bindings/schemas/service";
 
 const addr = process.env.SERVER_ADDR;
 if (!addr) throw new Error("SERVER_ADDR not set");
@@ -38,8 +39,8 @@ const TEST_STRUCTURE: Structure = {
 async function checkServer(url: string) {
   const ws = new WebSocket(url);
 
-  const rpc = new RpcClient<TestRequest, TestResponse>((packet) => {
-    ws.send(JSON.stringify(packet));
+  const rpc = new RpcClient<TestRequest, TestResponse>((envelope) => {
+    ws.send(JSON.stringify(envelope));
   });
 
   ws.on("message", (data) => {
@@ -57,6 +58,7 @@ async function checkServer(url: string) {
 
   // Wait until we're connected to the server.
   await new Promise((resolve) => ws.on("open", resolve));
+  rpc.hello(TEST_FINGERPRINT);
 
   //// Test the service.
   const client = TestClient(rpc);
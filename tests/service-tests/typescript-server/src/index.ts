@@ -1,4 +1,4 @@
-import type { Packet, Result } from "rawr-json";
+import type { Envelope, Packet, Result } from "rawr-json";
 import {
   TestServer,
   type TestRequest,
@@ -29,7 +29,19 @@ Bun.serve({
   },
   websocket: {
     async message(ws, message) {
-      const req: Packet<TestRequest> = JSON.parse(message as any);
+      const req: Envelope<TestRequest> = JSON.parse(message as any);
+      // Answer heartbeats, so that clients don't take the server for gone.
+      if ("ping" in req) {
+        ws.send(JSON.stringify({ pong: true }));
+        return;
+      }
+      if ("batch" in req) {
+        const batch = await Promise.all(req.batch.map(handleRequest));
+        ws.send(JSON.stringify({ batch }));
+        return;
+      }
+      // Requests are answered as a whole, so there's nothing to cancel.
+      if (!("data" in req)) return;
       const res: Packet<Result<TestResponse>> = await handleRequest(req);
       ws.send(JSON.stringify(res));
     },
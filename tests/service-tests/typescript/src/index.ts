@@ -5,7 +5,7 @@ import {
   type TestRequest,
   type TestResponse,
 } from "../../manual-codegen";
-import { RpcClient } from "rawr-json";
+import { RpcClient, RpcPeer } from "rawr-json";
 
 async function main() {
   const handleRequest = TestServer({
@@ -22,8 +22,10 @@ async function main() {
     },
   });
 
-  const rpc = new RpcClient<TestRequest, TestResponse>((packet) => {
-    handleRequest(packet).then((res) => {
+  const rpc = new RpcClient<TestRequest, TestResponse>((envelope) => {
+    // The in-memory server only answers whole requests.
+    if (!("data" in envelope)) return;
+    handleRequest(envelope).then((res) => {
       rpc.handleResponse(res);
     });
   });
@@ -38,6 +40,31 @@ async function main() {
       }
     });
   }
+
+  //// Notifications between peers
+
+  const events: string[] = [];
+  const a = new RpcPeer<TestRequest, TestResponse, TestRequest, TestResponse>(
+    handleRequest,
+    (message) => b.handleMessage(message)
+  );
+  const b = new RpcPeer<TestRequest, TestResponse, TestRequest, TestResponse>(
+    handleRequest,
+    (message) => a.handleMessage(message),
+    (notification, meta) => {
+      if (notification.method === "log_event") {
+        events.push(`${notification.payload[0]} (${meta?.trace})`);
+      }
+    }
+  );
+
+  // Notifications carry the metadata of the client, like requests.
+  a.client.meta = { trace: "abc" };
+  TestClient(a.client).log_event("notified");
+  await sleep(0);
+  if (events.join() !== "notified (abc)") {
+    throw new Error(`Expected the notification to be handled, got ${events}`);
+  }
 }
 
 function sleep(ms: number) {